@@ -1,4 +1,9 @@
 use crate::minesweeper::*;
+use crate::placement::{placement_from_name, MinePlacement, Uniform, PLACEMENT_NAMES};
+use crate::probability::{self, Probabilities};
+use crate::replay::{Action, Replay};
+use crate::solver::{self, Deduction};
+use crate::topology::Cubic;
 
 use ggez::event::{self, EventHandler, KeyCode, KeyMods, MouseButton};
 use ggez::filesystem;
@@ -10,15 +15,19 @@ use std::env;
 use std::fmt::Display;
 use std::io::{Read, Write};
 use std::path;
+use std::rc::Rc;
 
 #[derive(Debug)]
 pub struct MinesweeperGame {
-    board: Board,
+    game: Game,
     diff: DifficultySetting,
     custom_diff: Difficulty,
+    first_click: FirstClickPolicy,
+    /// The number of layers new boards have, more than one making them 3D
+    depth: usize,
+    /// How the mines of custom boards are spread
+    placement: Rc<dyn MinePlacement>,
     state: GameState,
-    unflagged_mines: i32,
-    timer: f64,
     menu: MainMenu,
     popup: Option<PopupMenu>,
     hidden_image: graphics::Image,
@@ -38,6 +47,20 @@ pub struct MinesweeperGame {
     best_medium: u16,
     best_hard: u16,
     time_since_click: f64,
+    next_seed: Option<u64>,
+    show_hints: bool,
+    hints: Vec<Deduction>,
+    show_probabilities: bool,
+    probabilities: Probabilities,
+    replay: Replay,
+    /// Whether the end of the game has been handled: stats worked out and the replay saved
+    finished: bool,
+    stats: Option<GameStats>,
+    viewer: Option<ReplayViewer>,
+    /// Mines that were set off, so it is clear what lost the game
+    exploded: Vec<(usize, usize)>,
+    /// The layer of a 3D board being shown and played on
+    layer: usize,
 }
 
 #[derive(PartialEq, Debug)]
@@ -47,6 +70,7 @@ enum GameState {
     Updated,
     Loss,
     Win,
+    Replay,
 }
 #[derive(PartialEq, Copy, Clone, Debug)]
 enum DifficultySetting {
@@ -56,6 +80,17 @@ enum DifficultySetting {
     Custom,
 }
 
+impl DifficultySetting {
+    fn from_name(name: &str) -> DifficultySetting {
+        match name.trim() {
+            "medium" => DifficultySetting::Medium,
+            "hard" => DifficultySetting::Hard,
+            "custom" => DifficultySetting::Custom,
+            _ => DifficultySetting::Easy,
+        }
+    }
+}
+
 impl Display for DifficultySetting {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use DifficultySetting::*;
@@ -96,21 +131,82 @@ const BUTTON_BG: Color = Color {
     g: 0.5,
     a: 1.,
 };
+const HINT_SAFE: Color = Color {
+    r: 0.,
+    b: 0.,
+    g: 0.8,
+    a: 1.,
+};
+const HINT_MINE: Color = Color {
+    r: 0.9,
+    b: 0.,
+    g: 0.,
+    a: 1.,
+};
 const TEXT_BG: Color = Color {
     r: 0.8,
     b: 0.8,
     g: 0.8,
     a: 1.,
 };
+const CURSOR_REVEAL: Color = Color {
+    r: 0.,
+    b: 0.9,
+    g: 0.3,
+    a: 1.,
+};
+const CURSOR_TOGGLE: Color = Color {
+    r: 0.9,
+    b: 0.,
+    g: 0.5,
+    a: 1.,
+};
+const CURSOR_CHORD: Color = Color {
+    r: 0.6,
+    b: 0.8,
+    g: 0.,
+    a: 1.,
+};
+const EXPLODED: Color = Color {
+    r: 1.,
+    b: 0.,
+    g: 0.,
+    a: 0.4,
+};
+
+/// How much the layers either side of the one being played on are shrunk and faded.
+const GHOST_SCALE: f32 = 0.5;
+const GHOST_ALPHA: f32 = 0.35;
+/// The most layers the menu offers.
+const MAX_DEPTH: usize = 5;
+
+/// Height of each line of stats in the win and loss popups.
+const DETAILS_LINE_HEIGHT: f32 = 12.;
+
+/// Playback speeds a replay can be watched at.
+const REPLAY_SPEEDS: [f64; 6] = [0.25, 0.5, 1., 2., 4., 8.];
+const NORMAL_SPEED: usize = 2;
+/// How far the arrow keys seek through a replay, in seconds.
+const SEEK_TIME: f64 = 5.;
+
+const SAVE_FILE: &str = "/save";
+const SAVE_HEADER: &str = "minesweeper game";
+/// Version of the game settings written before the board in the save file. Version 1 saves also
+/// hold the number of mines left unflagged, which is now counted from the board.
+const SAVE_VERSION: u32 = 2;
+/// The replay of the last finished game is kept here, replacing the one before it.
+const REPLAY_FILE: &str = "/last.replay";
 
-const DEFAULT_CONFIG: &'static str = "easy
+const DEFAULT_CONFIG: &str = "easy
 24,16,50
 999
 999
-999";
+999
+false
+1";
 
 impl MinesweeperGame {
-    pub fn new(ctx: &mut Context) -> GameResult<MinesweeperGame> {
+    pub fn new(ctx: &mut Context, seed: Option<u64>) -> GameResult<MinesweeperGame> {
         let mut config = String::new();
         {
             if !filesystem::exists(ctx, "/config") {
@@ -127,9 +223,7 @@ impl MinesweeperGame {
             file.read_to_string(&mut config)?;
         }
         let config: Vec<&str> = config.trim().split('\n').collect();
-        let config_diff = config[0].trim();
-        let board: Board;
-        let diff: DifficultySetting;
+        let diff = DifficultySetting::from_name(config[0]);
         let custom: Vec<usize> = config[1]
             .split(',')
             .map(|s| s.trim().parse().unwrap())
@@ -138,22 +232,23 @@ impl MinesweeperGame {
         let best_easy: u16 = config[2].trim().parse().unwrap();
         let best_medium: u16 = config[3].trim().parse().unwrap();
         let best_hard: u16 = config[4].trim().parse().unwrap();
-        if config_diff == "easy" {
-            board = Board::new(EASY.0, EASY.1, EASY.2);
-            diff = DifficultySetting::Easy;
-        } else if config_diff == "medium" {
-            board = Board::new(MEDIUM.0, MEDIUM.1, MEDIUM.2);
-            diff = DifficultySetting::Medium;
-        } else if config_diff == "hard" {
-            board = Board::new(HARD.0, HARD.1, HARD.2);
-            diff = DifficultySetting::Hard
-        } else if config_diff == "custom" {
-            board = Board::new(custom_diff.0, custom_diff.1, custom_diff.2);
-            diff = DifficultySetting::Custom;
-        } else {
-            board = Board::new(EASY.0, EASY.1, EASY.2);
-            diff = DifficultySetting::Easy;
-        }
+        // Older config files don't have the first click policy, or only say whether it is no guess
+        let first_click = match config.get(5).map(|line| line.trim()) {
+            Some("true") => FirstClickPolicy::NoGuess,
+            Some(name) => FirstClickPolicy::from_name(name).unwrap_or(FirstClickPolicy::Opening),
+            None => FirstClickPolicy::Opening,
+        };
+        // or the number of layers
+        let depth = config
+            .get(6)
+            .and_then(|line| line.trim().parse().ok())
+            .map_or(1, |depth: usize| depth.clamp(1, MAX_DEPTH));
+        // or the mine placement
+        let placement = config
+            .get(7)
+            .and_then(|line| placement_from_name(line.trim()))
+            .unwrap_or_else(|| Rc::new(Uniform));
+        let board = create_board(diff, &custom_diff, seed, first_click, depth, &placement);
 
         let hidden_image = graphics::Image::new(ctx, "/hidden.png")?;
         let flag_image = graphics::Image::new(ctx, "/flag.png")?;
@@ -169,17 +264,23 @@ impl MinesweeperGame {
         let eight_image = graphics::Image::new(ctx, "/eight.png")?;
         let mine_image = graphics::Image::new(ctx, "/mine.png")?;
 
-        let unflagged_mines = board.mines as i32;
-
-        let menu = MainMenu::new(diff, Difficulty(24, 16, 50));
+        let menu = MainMenu::new(
+            diff,
+            Difficulty(24, 16, 50),
+            first_click,
+            depth,
+            placement.clone(),
+        );
+        let replay = Replay::new(&board);
 
-        let game = MinesweeperGame {
-            board,
+        let mut game = MinesweeperGame {
+            game: Game::new(board),
             diff,
             custom_diff,
+            first_click,
+            depth,
+            placement,
             state: GameState::Updated,
-            unflagged_mines,
-            timer: 0.0,
             menu,
             popup: None,
             hidden_image,
@@ -199,279 +300,498 @@ impl MinesweeperGame {
             best_medium,
             best_hard,
             time_since_click: 1.0,
+            next_seed: None,
+            show_hints: false,
+            hints: Vec::new(),
+            show_probabilities: false,
+            probabilities: Vec::new(),
+            replay,
+            finished: false,
+            stats: None,
+            viewer: None,
+            exploded: Vec::new(),
+            layer: 0,
         };
+        // Pick up where the last session left off, unless a specific board was asked for
+        if seed.is_none() {
+            if let Err(message) = game.load_game(ctx) {
+                eprintln!("Unable to load the saved game: {}", message);
+            }
+        }
         game.init_window_size(ctx)?;
         Ok(game)
     }
 
     fn init_window_size(&self, ctx: &mut Context) -> GameResult {
-        set_window_size(
-            ctx,
-            (self.board.width as f32) * TILE_SIZE,
-            ((self.board.height + 1) as f32) * TILE_SIZE,
-        )
+        let board = self.board();
+        // A 3D board has room for the layers either side of the one being played on
+        let width = (board.width as f32) * TILE_SIZE;
+        let width = if board.depth() > 1 {
+            width * (1. + 2. * GHOST_SCALE)
+        } else {
+            width
+        };
+        set_window_size(ctx, width, ((board.layer_height() + 1) as f32) * TILE_SIZE)
     }
 
-    fn new_game(&mut self, ctx: &mut Context) -> GameResult {
-        match self.diff {
-            DifficultySetting::Custom => {
-                self.board = Board::new(self.custom_diff.0, self.custom_diff.1, self.custom_diff.2)
-            }
-            DifficultySetting::Easy => self.board = Board::new(EASY.0, EASY.1, EASY.2),
-            DifficultySetting::Medium => self.board = Board::new(MEDIUM.0, MEDIUM.1, MEDIUM.2),
-            DifficultySetting::Hard => self.board = Board::new(HARD.0, HARD.1, HARD.2),
+    /// Moves to another layer of a 3D board.
+    fn change_layer(&mut self, up: bool) {
+        if up {
+            self.layer = self.layer.saturating_sub(1);
+        } else {
+            self.layer = (self.layer + 1).min(self.board().depth() - 1);
+        }
+        if self.state == GameState::Default {
+            self.state = GameState::Updated;
+        }
+    }
+
+    /// The board being shown: the replay's while one is being watched, otherwise the game's.
+    fn board(&self) -> &Board {
+        match &self.viewer {
+            Some(viewer) => &viewer.board,
+            None => self.game.board(),
         }
+    }
+
+    fn new_game(&mut self, ctx: &mut Context) -> GameResult {
+        let board = create_board(
+            self.diff,
+            &self.custom_diff,
+            self.next_seed.take(),
+            self.first_click,
+            self.depth,
+            &self.placement,
+        );
+        self.replay = Replay::new(&board);
+        self.game = Game::new(board);
         self.state = GameState::Updated;
-        self.timer = 0.0;
-        self.unflagged_mines = self.board.mines as i32;
+        self.finished = false;
+        self.exploded.clear();
+        self.layer = 0;
+        self.init_window_size(ctx)
+    }
+
+    /// Switches to watching a replay. The game in progress is put aside until `stop_replay`.
+    fn start_replay(&mut self, ctx: &mut Context, replay: Replay) -> GameResult {
+        let viewer = ReplayViewer::new(replay)
+            .map_err(|e| ggez::GameError::ResourceLoadError(e.to_string()))?;
+        self.viewer = Some(viewer);
+        self.popup = None;
+        self.state = GameState::Replay;
+        self.layer = 0;
+        self.init_window_size(ctx)
+    }
+
+    fn stop_replay(&mut self, ctx: &mut Context) -> GameResult {
+        self.viewer = None;
+        self.layer = 0;
+        self.phase_changed();
         self.init_window_size(ctx)
     }
 
+    fn load_replay(&mut self, ctx: &mut Context) -> GameResult {
+        let mut save = String::new();
+        filesystem::open(ctx, REPLAY_FILE)?.read_to_string(&mut save)?;
+        let replay = Replay::load(&save).map_err(ggez::GameError::ResourceLoadError)?;
+        self.start_replay(ctx, replay)
+    }
+
     fn check(&mut self, x: usize, y: usize) {
-        let display = self.board.get_display_at(x, y);
+        let display = self.game.board().get_display_at(x, y);
         if display == Ok(TileDisplay::Hidden) {
-            match self.board.reveal_at(x, y) {
-                Ok(tile) => match tile {
-                    Tile::Mine => self.state = GameState::Loss,
-                    _ => self.state = GameState::Updated,
-                },
-                Err(message) => eprintln!("{}", message),
+            match self.game.reveal(x, y) {
+                Ok(_) => {
+                    self.record(Action::Reveal(x, y));
+                    self.phase_changed();
+                }
+                // Clicks after the game has ended or outside the board's shape are ignored
+                Err(BoardError::GameOver) | Err(BoardError::OutsideShape { .. }) => (),
+                Err(e) => eprintln!("Unable to reveal ({}, {}): {}", x, y, e),
             }
         }
     }
 
+    /// Moves to the state that matches the phase of the game.
+    fn phase_changed(&mut self) {
+        self.state = match self.game.phase() {
+            Phase::Lost => GameState::Loss,
+            Phase::Won => GameState::Win,
+            _ => GameState::Updated,
+        };
+    }
+
     fn toggle(&mut self, x: usize, y: usize) {
-        match self.board.toggle_display_at(x, y) {
-            Err(message) => eprintln!("{}", message),
-            Ok(display) => {
-                if display == TileDisplay::Flag {
-                    self.unflagged_mines -= 1;
-                } else if display == TileDisplay::Question {
-                    self.unflagged_mines += 1;
-                }
+        match self.game.toggle(x, y) {
+            Ok(_) => {
+                self.record(Action::Toggle(x, y));
                 self.state = GameState::Updated;
             }
+            Err(BoardError::AlreadyRevealed { .. })
+            | Err(BoardError::GameOver)
+            | Err(BoardError::OutOfBounds { .. })
+            | Err(BoardError::OutsideShape { .. }) => (),
+            Err(e) => eprintln!("Unable to flag ({}, {}): {}", x, y, e),
         }
     }
 
     fn chord(&mut self, x: usize, y: usize) {
-        let req_flags = if let Ok(tile) = self.board.get_tile_at(x, y) {
-            match tile {
-                Tile::Mine => return (),
-                Tile::Safe(digit) => Digit::to_int(digit),
+        match self.game.chord(x, y) {
+            Ok(_) => {
+                self.record(Action::Chord(x, y));
+                self.phase_changed();
             }
-        } else {
-            return ();
-        };
-        let mut count_adj_flags = 0;
-        if x > 0 {
-            // Check left
-            count_adj_flags += match self.board.get_display_at(x - 1, y) {
-                Ok(display) => match display {
-                    TileDisplay::Flag => 1,
-                    _ => 0,
-                },
-                _ => 0,
-            };
-            // Check Down-Left
-            count_adj_flags += match self.board.get_display_at(x - 1, y + 1) {
-                Ok(display) => match display {
-                    TileDisplay::Flag => 1,
-                    _ => 0,
-                },
-                _ => 0,
-            };
-            if y > 0 {
-                // Check Up-Left
-                count_adj_flags += match self.board.get_display_at(x - 1, y - 1) {
-                    Ok(display) => match display {
-                        TileDisplay::Flag => 1,
-                        _ => 0,
-                    },
-                    _ => 0,
-                };
+            Err(BoardError::NotRevealed { .. })
+            | Err(BoardError::WrongFlagCount { .. })
+            | Err(BoardError::GameOver)
+            | Err(BoardError::OutOfBounds { .. })
+            | Err(BoardError::OutsideShape { .. }) => (),
+            Err(e) => eprintln!("Unable to chord ({}, {}): {}", x, y, e),
+        }
+    }
+
+    /// Saves the game in progress so it can be resumed later. If no game is in progress any old
+    /// save is removed instead.
+    fn save_game(&self, ctx: &mut Context) -> GameResult {
+        if self.game.phase() != Phase::Playing {
+            if filesystem::exists(ctx, SAVE_FILE) {
+                filesystem::delete(ctx, SAVE_FILE)?;
             }
+            return Ok(());
         }
-        // Check Down-Right
-        count_adj_flags += match self.board.get_display_at(x + 1, y + 1) {
-            Ok(display) => match display {
-                TileDisplay::Flag => 1,
-                _ => 0,
-            },
-            _ => 0,
-        };
-        // Check Right
-        count_adj_flags += match self.board.get_display_at(x + 1, y) {
-            Ok(display) => match display {
-                TileDisplay::Flag => 1,
-                _ => 0,
-            },
-            _ => 0,
-        };
-        // Check Down
-        count_adj_flags += match self.board.get_display_at(x, y + 1) {
-            Ok(display) => match display {
-                TileDisplay::Flag => 1,
-                _ => 0,
-            },
-            _ => 0,
-        };
-        if y > 0 {
-            // Check Up-Right
-            count_adj_flags += match self.board.get_display_at(x + 1, y - 1) {
-                Ok(display) => match display {
-                    TileDisplay::Flag => 1,
-                    _ => 0,
-                },
-                _ => 0,
-            };
-            // Check Up
-            count_adj_flags += match self.board.get_display_at(x, y - 1) {
-                Ok(display) => match display {
-                    TileDisplay::Flag => 1,
-                    _ => 0,
-                },
-                _ => 0,
-            };
+        let mut save_file = filesystem::create(ctx, SAVE_FILE)?;
+        writeln!(save_file, "{} {}", SAVE_HEADER, SAVE_VERSION)?;
+        writeln!(save_file, "{}", self.diff)?;
+        writeln!(save_file, "{}", self.custom_diff)?;
+        writeln!(save_file, "{}", self.game.time())?;
+        write!(save_file, "{}", self.game.board().save())?;
+        Ok(())
+    }
+
+    /// Resumes the game stored by `save_game`, if there is one.
+    fn load_game(&mut self, ctx: &mut Context) -> Result<(), String> {
+        if !filesystem::exists(ctx, SAVE_FILE) {
+            return Ok(());
         }
-        if count_adj_flags == req_flags {
-            match self.board.reveal_adjacent(x, y) {
-                Ok(hit_mine) => {
-                    if hit_mine {
-                        self.state = GameState::Loss;
-                        self.board.reveal_all();
-                        return;
-                    }
+        let mut save = String::new();
+        filesystem::open(ctx, SAVE_FILE)
+            .and_then(|mut file| Ok(file.read_to_string(&mut save)?))
+            .map_err(|e| e.to_string())?;
+        let saved = read_save(&save)?;
+
+        self.diff = saved.diff;
+        self.custom_diff = saved.custom_diff;
+        self.replay = Replay::new(&saved.board);
+        self.game = Game::resume(saved.board, saved.time);
+        self.exploded.clear();
+        self.layer = 0;
+        self.state = GameState::Updated;
+        self.popup = None;
+        self.finished = false;
+        Ok(())
+    }
+
+    fn undo(&mut self) {
+        let undone = self.game.undo();
+        for _ in 0..undone {
+            self.record(Action::Undo);
+        }
+        if undone > 0 {
+            self.history_changed();
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.game.redo() {
+            self.record(Action::Redo);
+            self.history_changed();
+        }
+    }
+
+    fn history_changed(&mut self) {
+        self.popup = None;
+        // The game goes on, so the replay will be saved again when it next ends
+        self.finished = false;
+        self.phase_changed();
+    }
+
+    fn record(&mut self, action: Action) {
+        self.replay.record(self.game.time(), action);
+    }
+
+    /// Works out the stats of the game that just ended and saves its replay.
+    fn finish_game(&mut self, ctx: &mut Context) {
+        self.finished = true;
+        self.stats = Some(self.game.stats());
+        if self.state == GameState::Loss {
+            self.game.reveal_all();
+            self.record(Action::RevealAll);
+        }
+        if let Err(e) = self.save_replay(ctx) {
+            eprintln!("Unable to save the replay: {}", e);
+        }
+    }
+
+    /// Writes the replay of the game that just ended to `REPLAY_FILE`.
+    fn save_replay(&mut self, ctx: &mut Context) -> GameResult {
+        self.replay.finish(self.game.board());
+        let mut replay_file = filesystem::create(ctx, REPLAY_FILE)?;
+        write!(replay_file, "{}", self.replay.save())?;
+        Ok(())
+    }
+
+    /// Where the left edge of the layer being played on is drawn. On a 3D board there is room
+    /// to its left for the layer before it.
+    fn board_left(&self) -> f32 {
+        let board = self.board();
+        if board.depth() > 1 {
+            (board.width as f32) * TILE_SIZE * GHOST_SCALE
+        } else {
+            0.
+        }
+    }
+
+    /// Returns the screen area of the tile at (x, y), if it is on the layer being played on.
+    fn tile_rect(&self, x: usize, y: usize) -> Option<Rect> {
+        let rows = self.board().layer_height();
+        if y / rows != self.layer {
+            return None;
+        }
+        Some(Rect::new(
+            self.board_left() + (x as f32) * TILE_SIZE,
+            ((y % rows) as f32) * TILE_SIZE,
+            TILE_SIZE,
+            TILE_SIZE,
+        ))
+    }
+
+    /// Returns the tile under a point on the screen, if there is one.
+    fn tile_at(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        let board = self.board();
+        let x = x - self.board_left();
+        if x < 0. || y < 0. {
+            return None;
+        }
+        let (column, row) = ((x / TILE_SIZE) as usize, (y / TILE_SIZE) as usize);
+        if column >= board.width || row >= board.layer_height() {
+            return None;
+        }
+        Some((column, self.layer * board.layer_height() + row))
+    }
+
+    /// Draws layer `z` of the board with its top left corner at `origin`, with tiles scaled down
+    /// by `scale` and faded by `alpha`.
+    fn draw_layer(
+        &self,
+        ctx: &mut Context,
+        z: usize,
+        origin: Point2<f32>,
+        scale: f32,
+        alpha: f32,
+    ) -> GameResult {
+        let board = self.board();
+        let rows = board.layer_height();
+        let tile_size = TILE_SIZE * scale;
+        let tint = Color::new(1., 1., 1., alpha);
+        for x in 0..board.width {
+            for row in 0..rows {
+                let y = z * rows + row;
+                if board.get_tile_at(x, y) == Ok(Tile::Void) {
+                    continue;
                 }
-                Err(message) => eprintln!("{}", message),
+                let dest = Point2::new(
+                    origin.x + (x as f32) * tile_size,
+                    origin.y + (row as f32) * tile_size,
+                );
+                let params = graphics::DrawParam::new()
+                    .dest(dest)
+                    .scale([scale, scale])
+                    .color(tint);
+                let image = match board.get_display_at(x, y).unwrap() {
+                    TileDisplay::Revealed => match board.get_tile_at(x, y).unwrap() {
+                        Tile::Mine => &self.mine_image,
+                        Tile::Void => continue,
+                        Tile::Safe(Count(0)) => &self.zero_image,
+                        Tile::Safe(Count(1)) => &self.one_image,
+                        Tile::Safe(Count(2)) => &self.two_image,
+                        Tile::Safe(Count(3)) => &self.three_image,
+                        Tile::Safe(Count(4)) => &self.four_image,
+                        Tile::Safe(Count(5)) => &self.five_image,
+                        Tile::Safe(Count(6)) => &self.six_image,
+                        Tile::Safe(Count(7)) => &self.seven_image,
+                        Tile::Safe(Count(8)) => &self.eight_image,
+                        // There are no images past eight, so larger counts are written out
+                        Tile::Safe(Count(count)) => {
+                            graphics::draw(ctx, &self.zero_image, params)?;
+                            let fragment = graphics::TextFragment::new(count.to_string())
+                                .scale(graphics::Scale::uniform(14. * scale));
+                            graphics::draw(
+                                ctx,
+                                &graphics::Text::new(fragment),
+                                graphics::DrawParam::default()
+                                    .dest(Point2::new(dest.x + 5. * scale, dest.y + 6. * scale))
+                                    .color(Color::new(0., 0., 0., alpha)),
+                            )?;
+                            continue;
+                        }
+                    },
+                    TileDisplay::Hidden => &self.hidden_image,
+                    TileDisplay::Flag => &self.flag_image,
+                    TileDisplay::Question => &self.question_image,
+                };
+                graphics::draw(ctx, image, params)?;
             }
-            self.state = GameState::Updated;
         }
+        Ok(())
     }
 
-    fn draw_board(&mut self, ctx: &mut Context) -> GameResult {
+    fn draw_board(&self, ctx: &mut Context) -> GameResult {
         graphics::clear(ctx, graphics::WHITE);
-        for x in 0..self.board.width {
-            for y in 0..self.board.height {
-                let dest = Point2::new((x as f32) * TILE_SIZE, (y as f32) * TILE_SIZE);
-                let tile_display = self.board.get_display_at(x, y).unwrap();
-                match tile_display {
-                    TileDisplay::Revealed => {
-                        let tile = self.board.get_tile_at(x, y).unwrap();
-                        match tile {
-                            Tile::Mine => {
-                                graphics::draw(
-                                    ctx,
-                                    &self.mine_image,
-                                    graphics::DrawParam::new().dest(dest),
-                                )?;
-                            }
-                            Tile::Safe(Digit::Zero) => {
-                                graphics::draw(
-                                    ctx,
-                                    &self.zero_image,
-                                    graphics::DrawParam::new().dest(dest),
-                                )?;
-                            }
-                            Tile::Safe(Digit::One) => {
-                                graphics::draw(
-                                    ctx,
-                                    &self.one_image,
-                                    graphics::DrawParam::new().dest(dest),
-                                )?;
-                            }
-                            Tile::Safe(Digit::Two) => {
-                                graphics::draw(
-                                    ctx,
-                                    &self.two_image,
-                                    graphics::DrawParam::new().dest(dest),
-                                )?;
-                            }
-                            Tile::Safe(Digit::Three) => {
-                                graphics::draw(
-                                    ctx,
-                                    &self.three_image,
-                                    graphics::DrawParam::new().dest(dest),
-                                )?;
-                            }
-                            Tile::Safe(Digit::Four) => {
-                                graphics::draw(
-                                    ctx,
-                                    &self.four_image,
-                                    graphics::DrawParam::new().dest(dest),
-                                )?;
-                            }
-                            Tile::Safe(Digit::Five) => {
-                                graphics::draw(
-                                    ctx,
-                                    &self.five_image,
-                                    graphics::DrawParam::new().dest(dest),
-                                )?;
-                            }
-                            Tile::Safe(Digit::Six) => {
-                                graphics::draw(
-                                    ctx,
-                                    &self.six_image,
-                                    graphics::DrawParam::new().dest(dest),
-                                )?;
-                            }
-                            Tile::Safe(Digit::Seven) => {
-                                graphics::draw(
-                                    ctx,
-                                    &self.seven_image,
-                                    graphics::DrawParam::new().dest(dest),
-                                )?;
-                            }
-                            Tile::Safe(_) => {
-                                graphics::draw(
-                                    ctx,
-                                    &self.eight_image,
-                                    graphics::DrawParam::new().dest(dest),
-                                )?;
-                            }
-                        }
-                    }
-                    TileDisplay::Hidden => {
-                        graphics::draw(
-                            ctx,
-                            &self.hidden_image,
-                            graphics::DrawParam::new().dest(dest),
-                        )?;
-                    }
-                    TileDisplay::Flag => {
-                        graphics::draw(
-                            ctx,
-                            &self.flag_image,
-                            graphics::DrawParam::new().dest(dest),
-                        )?;
-                    }
-                    TileDisplay::Question => {
+        let board = self.board();
+        let left = self.board_left();
+        self.draw_layer(ctx, self.layer, Point2::new(left, 0.), 1., 1.)?;
+        // The layers either side of a 3D board are shown faded and shrunk beside it
+        if self.layer > 0 {
+            self.draw_layer(
+                ctx,
+                self.layer - 1,
+                Point2::new(0., 0.),
+                GHOST_SCALE,
+                GHOST_ALPHA,
+            )?;
+        }
+        if self.layer + 1 < board.depth() {
+            self.draw_layer(
+                ctx,
+                self.layer + 1,
+                Point2::new(left + (board.width as f32) * TILE_SIZE, 0.),
+                GHOST_SCALE,
+                GHOST_ALPHA,
+            )?;
+        }
+        if self.viewer.is_none() && !self.exploded.is_empty() {
+            let mut exploded_mesh = graphics::MeshBuilder::new();
+            let mut any_shown = false;
+            for &(x, y) in self.exploded.iter() {
+                if let Some(bounds) = self.tile_rect(x, y) {
+                    exploded_mesh.rectangle(DrawMode::fill(), bounds, EXPLODED);
+                    any_shown = true;
+                }
+            }
+            if any_shown {
+                let exploded_mesh = exploded_mesh.build(ctx)?;
+                graphics::draw(ctx, &exploded_mesh, graphics::DrawParam::new())?;
+            }
+        }
+        if self.show_hints && !self.hints.is_empty() {
+            let mut hints_mesh = graphics::MeshBuilder::new();
+            let mut any_shown = false;
+            for hint in self.hints.iter() {
+                if let Some(tile) = self.tile_rect(hint.x, hint.y) {
+                    let bounds =
+                        Rect::new(tile.x + 2., tile.y + 2., TILE_SIZE - 4., TILE_SIZE - 4.);
+                    let color = if hint.mine { HINT_MINE } else { HINT_SAFE };
+                    hints_mesh.rectangle(DrawMode::stroke(2.), bounds, color);
+                    any_shown = true;
+                }
+            }
+            if any_shown {
+                let hints_mesh = hints_mesh.build(ctx)?;
+                graphics::draw(ctx, &hints_mesh, graphics::DrawParam::new())?;
+            }
+        }
+        if self.show_probabilities {
+            for (x, column) in self.probabilities.iter().enumerate() {
+                for (y, probability) in column.iter().enumerate() {
+                    if let (Some(p), Some(tile)) = (probability, self.tile_rect(x, y)) {
+                        let fragment =
+                            graphics::TextFragment::new(format!("{}", (p * 100.).round()))
+                                .scale(graphics::Scale::uniform(12.));
+                        let text = graphics::Text::new(fragment);
                         graphics::draw(
                             ctx,
-                            &self.question_image,
-                            graphics::DrawParam::new().dest(dest),
+                            &text,
+                            graphics::DrawParam::default()
+                                .dest(Point2::new(tile.x + 4., tile.y + 7.))
+                                .color(graphics::BLACK),
                         )?;
                     }
                 }
             }
         }
-        let timer_string = format!("{}", self.timer as i32);
+        let (time, mines_left) = match &self.viewer {
+            Some(viewer) => (
+                viewer.clock,
+                board.mines as i64 - board.count_flags() as i64,
+            ),
+            None => (self.game.time(), self.game.mines_left()),
+        };
+        let bottom = (board.layer_height() as f32) * TILE_SIZE + 5.;
+        let timer_string = format!("{}", time.min(999.) as i32);
         let timer_text = graphics::Text::new(timer_string);
         graphics::draw(
             ctx,
             &timer_text,
             graphics::DrawParam::default()
-                .dest(Point2::new(0., (self.board.height as f32) * TILE_SIZE + 5.))
+                .dest(Point2::new(left, bottom))
                 .color(graphics::BLACK),
         )?;
-        let mines_string = format!("Mines: {}", self.unflagged_mines);
+        let mines_string = format!("Mines: {}", mines_left);
         let mines_text = graphics::Text::new(mines_string);
-        let x = (self.board.width as f32) * TILE_SIZE - 100.;
+        let x = left + (board.width as f32) * TILE_SIZE - 100.;
         graphics::draw(
             ctx,
             &mines_text,
             graphics::DrawParam::default()
-                .dest(Point2::new(x, (self.board.height as f32) * TILE_SIZE + 5.))
+                .dest(Point2::new(x, bottom))
+                .color(graphics::BLACK),
+        )?;
+        if board.depth() > 1 {
+            let layer_text =
+                graphics::Text::new(format!("Layer {}/{}", self.layer + 1, board.depth()));
+            graphics::draw(
+                ctx,
+                &layer_text,
+                graphics::DrawParam::default()
+                    .dest(Point2::new(0., bottom))
+                    .color(graphics::BLACK),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_replay_overlay(&self, ctx: &mut Context) -> GameResult {
+        let viewer = match &self.viewer {
+            Some(viewer) => viewer,
+            None => return Ok(()),
+        };
+        if let Some((tile, color)) = viewer
+            .last_click()
+            .and_then(|(x, y, color)| Some((self.tile_rect(x, y)?, color)))
+        {
+            let center = Point2::new(tile.x + TILE_SIZE / 2., tile.y + TILE_SIZE / 2.);
+            let cursor = graphics::MeshBuilder::new()
+                .circle(
+                    DrawMode::stroke(3.),
+                    center,
+                    TILE_SIZE / 2. - 2.,
+                    0.1,
+                    color,
+                )
+                .build(ctx)?;
+            graphics::draw(ctx, &cursor, graphics::DrawParam::new())?;
+        }
+        let status = if viewer.playing { "Playing" } else { "Paused" };
+        let status_text =
+            graphics::Text::new(format!("x{} {}", REPLAY_SPEEDS[viewer.speed], status));
+        graphics::draw(
+            ctx,
+            &status_text,
+            graphics::DrawParam::default()
+                .dest(Point2::new(
+                    self.board_left() + 35.,
+                    (viewer.board.layer_height() as f32) * TILE_SIZE + 5.,
+                ))
                 .color(graphics::BLACK),
         )?;
         Ok(())
@@ -481,43 +801,65 @@ impl MinesweeperGame {
 impl EventHandler for MinesweeperGame {
     fn update(&mut self, _ctx: &mut Context) -> GameResult {
         if self.state == GameState::Default || self.state == GameState::Updated {
-            self.timer += timer::delta(_ctx).as_secs_f64();
+            self.game.tick(timer::delta(_ctx).as_secs_f64());
         }
         self.time_since_click += timer::delta(_ctx).as_secs_f64();
-        if self.timer > 999. {
-            self.timer = 999.
-        }
-        while timer::check_update_time(_ctx, 60) {
-            ()
+        while timer::check_update_time(_ctx, 60) {}
+        for event in self.game.drain_events() {
+            match event {
+                BoardEvent::MineExploded { x, y } => self.exploded.push((x, y)),
+                BoardEvent::MoveUndone => {
+                    let board = self.game.board();
+                    self.exploded
+                        .retain(|&(x, y)| board.get_display_at(x, y) == Ok(TileDisplay::Revealed));
+                }
+                _ => (),
+            }
         }
         match self.state {
             GameState::Updated => {
-                if self.board.check_victory() {
-                    self.state = GameState::Win;
+                if self.show_hints {
+                    self.hints = solver::solve(self.game.board());
+                }
+                if self.show_probabilities {
+                    self.probabilities = probability::mine_probabilities(self.game.board());
                 }
             }
             GameState::Menu => (),
+            GameState::Replay => {
+                if let Some(viewer) = &mut self.viewer {
+                    viewer.advance(timer::delta(_ctx).as_secs_f64());
+                }
+            }
             GameState::Loss => {
-                self.board.reveal_all();
+                if !self.finished {
+                    self.finish_game(_ctx);
+                }
             }
-            GameState::Win => match self.diff {
-                DifficultySetting::Easy => {
-                    if self.timer < self.best_easy as f64 {
-                        self.best_easy = self.timer as u16;
+            GameState::Win => {
+                let time = self.game.time();
+                // Best times are only kept for flat boards
+                let diff = if self.game.board().depth() == 1 {
+                    self.diff
+                } else {
+                    DifficultySetting::Custom
+                };
+                match diff {
+                    DifficultySetting::Easy if time < self.best_easy as f64 => {
+                        self.best_easy = time as u16;
                     }
-                }
-                DifficultySetting::Medium => {
-                    if self.timer < self.best_medium as f64 {
-                        self.best_medium = self.timer as u16;
+                    DifficultySetting::Medium if time < self.best_medium as f64 => {
+                        self.best_medium = time as u16;
                     }
-                }
-                DifficultySetting::Hard => {
-                    if self.timer < self.best_hard as f64 {
-                        self.best_hard = self.timer as u16;
+                    DifficultySetting::Hard if time < self.best_hard as f64 => {
+                        self.best_hard = time as u16;
                     }
+                    _ => (),
                 }
-                _ => (),
-            },
+                if !self.finished {
+                    self.finish_game(_ctx);
+                }
+            }
             GameState::Default => (),
         }
         Ok(())
@@ -534,7 +876,8 @@ impl EventHandler for MinesweeperGame {
                 if let Some(menu) = &self.popup {
                     menu.draw(ctx)?;
                 } else {
-                    let menu = PopupMenu::new("You Win!", "Restart", "Quit", 10., 10.);
+                    let details = self.stats.as_ref().map_or(String::new(), |s| s.to_string());
+                    let menu = PopupMenu::new("You Win!", details, "Restart", "Quit", 10., 10.);
                     menu.draw(ctx)?;
                     self.popup = Some(menu);
                 }
@@ -544,15 +887,19 @@ impl EventHandler for MinesweeperGame {
                 if let Some(menu) = &self.popup {
                     menu.draw(ctx)?;
                 } else {
-                    let menu = PopupMenu::new("You Lose!", "Retry?", "Quit", 10., 10.);
+                    let details = self.stats.as_ref().map_or(String::new(), |s| s.to_string());
+                    let menu = PopupMenu::new("You Lose!", details, "Retry?", "Quit", 10., 10.);
                     menu.draw(ctx)?;
                     self.popup = Some(menu);
                 }
-
             }
             GameState::Menu => {
                 self.menu.draw(ctx)?;
             }
+            GameState::Replay => {
+                self.draw_board(ctx)?;
+                self.draw_replay_overlay(ctx)?;
+            }
             _ => {
                 self.draw_board(ctx)?;
             }
@@ -563,8 +910,10 @@ impl EventHandler for MinesweeperGame {
     fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
         match self.state {
             GameState::Default => {
-                let x = (x / TILE_SIZE) as usize;
-                let y = (y / TILE_SIZE) as usize;
+                let (x, y) = match self.tile_at(x, y) {
+                    Some(tile) => tile,
+                    None => return,
+                };
                 match button {
                     MouseButton::Left => {
                         if self.time_since_click < DOUBLE_CLICK_TIME {
@@ -578,31 +927,27 @@ impl EventHandler for MinesweeperGame {
                     _ => (),
                 }
             }
-            GameState::Win => {
-                if button == MouseButton::Left {
-                    if let Some(menu) = &self.popup {
-                        let result = menu.mouse_button_down_event(x, y);
-                        if result == 1 {
-                            self.popup = None;
-                            self.new_game(ctx).unwrap();
-                        } else if result == 2 {
-                            self.quit_event(ctx);
-                            event::quit(ctx);
-                        }
+            GameState::Win if button == MouseButton::Left => {
+                if let Some(menu) = &self.popup {
+                    let result = menu.mouse_button_down_event(x, y);
+                    if result == 1 {
+                        self.popup = None;
+                        self.new_game(ctx).unwrap();
+                    } else if result == 2 {
+                        self.quit_event(ctx);
+                        event::quit(ctx);
                     }
                 }
             }
-            GameState::Loss => {
-                if button == MouseButton::Left {
-                    if let Some(menu) = &self.popup {
-                        let result = menu.mouse_button_down_event(x, y);
-                        if result == 1 {
-                            self.popup = None;
-                            self.new_game(ctx).unwrap();
-                        } else if result == 2 {
-                            self.quit_event(ctx);
-                            event::quit(ctx);
-                        }
+            GameState::Loss if button == MouseButton::Left => {
+                if let Some(menu) = &self.popup {
+                    let result = menu.mouse_button_down_event(x, y);
+                    if result == 1 {
+                        self.popup = None;
+                        self.new_game(ctx).unwrap();
+                    } else if result == 2 {
+                        self.quit_event(ctx);
+                        event::quit(ctx);
                     }
                 }
             }
@@ -612,6 +957,9 @@ impl EventHandler for MinesweeperGame {
                 {
                     self.diff = diff;
                     self.custom_diff = custom_diff;
+                    self.first_click = self.menu.first_click;
+                    self.depth = self.menu.depth;
+                    self.placement = self.menu.placement.clone();
                     self.new_game(ctx).unwrap();
                 }
             }
@@ -638,10 +986,60 @@ impl EventHandler for MinesweeperGame {
                     self.state = GameState::Updated;
                 }
             }
+            GameState::Replay => {
+                if let Some(viewer) = &mut self.viewer {
+                    match keycode {
+                        KeyCode::Space => viewer.playing = !viewer.playing,
+                        KeyCode::Left => viewer.seek(viewer.clock - SEEK_TIME),
+                        KeyCode::Right => viewer.seek(viewer.clock + SEEK_TIME),
+                        KeyCode::Home => viewer.seek(0.),
+                        KeyCode::End => viewer.seek(viewer.length()),
+                        KeyCode::Up => {
+                            viewer.speed = (viewer.speed + 1).min(REPLAY_SPEEDS.len() - 1)
+                        }
+                        KeyCode::Down => viewer.speed = viewer.speed.saturating_sub(1),
+                        KeyCode::PageUp => self.change_layer(true),
+                        KeyCode::PageDown => self.change_layer(false),
+                        KeyCode::Escape => self.stop_replay(ctx).unwrap(),
+                        _ => (),
+                    }
+                }
+            }
             _ => match keycode {
                 KeyCode::Space => {
                     self.state = GameState::Menu;
                 }
+                KeyCode::Z => self.undo(),
+                KeyCode::Y => self.redo(),
+                // Move between the layers of a 3D board
+                KeyCode::PageUp | KeyCode::Up => self.change_layer(true),
+                KeyCode::PageDown | KeyCode::Down => self.change_layer(false),
+                KeyCode::H => {
+                    // Outline every tile that can be deduced from the revealed numbers
+                    self.show_hints = !self.show_hints;
+                    if self.state == GameState::Default {
+                        self.state = GameState::Updated;
+                    }
+                }
+                KeyCode::P => {
+                    // Show the chance of a mine on every unrevealed tile
+                    self.show_probabilities = !self.show_probabilities;
+                    if self.state == GameState::Default {
+                        self.state = GameState::Updated;
+                    }
+                }
+                KeyCode::L => {
+                    // Watch the last finished game
+                    if let Err(e) = self.load_replay(ctx) {
+                        eprintln!("Unable to load the replay: {}", e);
+                    }
+                }
+                KeyCode::R => {
+                    // Restart the current board with the same layout
+                    self.next_seed = Some(self.game.board().seed);
+                    self.popup = None;
+                    self.new_game(ctx).unwrap();
+                }
                 KeyCode::Escape => {
                     self.quit_event(ctx);
                     event::quit(ctx);
@@ -652,6 +1050,9 @@ impl EventHandler for MinesweeperGame {
     }
 
     fn quit_event(&mut self, _ctx: &mut Context) -> bool {
+        if let Err(e) = self.save_game(_ctx) {
+            eprintln!("Unable to save the game: {}", e);
+        }
         {
             eprintln!("{}", self);
             eprintln!("Attempting to write to config file");
@@ -662,8 +1063,11 @@ impl EventHandler for MinesweeperGame {
             writeln!(config_file, "{}", self.best_easy).unwrap();
             writeln!(config_file, "{}", self.best_medium).unwrap();
             writeln!(config_file, "{}", self.best_hard).unwrap();
+            writeln!(config_file, "{}", self.first_click).unwrap();
+            writeln!(config_file, "{}", self.depth).unwrap();
+            writeln!(config_file, "{}", self.placement.name()).unwrap();
         }
-        return false;
+        false
     }
 }
 
@@ -671,8 +1075,15 @@ impl Display for MinesweeperGame {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(
             f,
-            "Difficulty Setting: {} Custom Settings: {} Best Times: Easy: {} Medium: {} Hard {}",
-            self.diff, self.custom_diff, self.best_easy, self.best_medium, self.best_hard
+            "Difficulty Setting: {} Custom Settings: {} First Click: {} Layers: {} Placement: {} Best Times: Easy: {} Medium: {} Hard {}",
+            self.diff,
+            self.custom_diff,
+            self.first_click,
+            self.depth,
+            self.placement.name(),
+            self.best_easy,
+            self.best_medium,
+            self.best_hard
         )
     }
 }
@@ -694,6 +1105,12 @@ struct MainMenu {
     custom_height_box: Rect,
     custom_mines: usize,
     custom_mines_box: Rect,
+    first_click_button: Rect,
+    first_click: FirstClickPolicy,
+    depth_button: Rect,
+    depth: usize,
+    placement_button: Rect,
+    placement: Rc<dyn MinePlacement>,
     confirm: &'static str,
     confirm_button: Rect,
     selected: DifficultySetting,
@@ -710,7 +1127,13 @@ enum MainMenuState {
 }
 
 impl MainMenu {
-    fn new(diff: DifficultySetting, custom_diff: Difficulty) -> MainMenu {
+    fn new(
+        diff: DifficultySetting,
+        custom_diff: Difficulty,
+        first_click: FirstClickPolicy,
+        depth: usize,
+        placement: Rc<dyn MinePlacement>,
+    ) -> MainMenu {
         let header = "Difficulty Width Height Mines";
         let easy = "Easy           9      9    10";
         let medium = "Medium      16     16    40";
@@ -727,7 +1150,11 @@ impl MainMenu {
         let custom_width_box = Rect::new(90., 120., 30., 30.);
         let custom_height_box = Rect::new(130., 120., 30., 30.);
         let custom_mines_box = Rect::new(170., 120., 30., 30.);
-        let menu = MainMenu {
+        let first_click_button = Rect::new(10., 160., 10., 10.);
+        let depth_button = Rect::new(10., 180., 10., 10.);
+        let placement_button = Rect::new(10., 200., 10., 10.);
+
+        MainMenu {
             header,
             easy,
             easy_button,
@@ -743,17 +1170,22 @@ impl MainMenu {
             custom_height_box,
             custom_mines,
             custom_mines_box,
+            first_click_button,
+            first_click,
+            depth_button,
+            depth,
+            placement_button,
+            placement,
             confirm,
             confirm_button,
             selected: diff,
             state: MainMenuState::Default,
             cursor: 0,
-        };
-        menu
+        }
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        set_window_size(ctx, 300., 180.)?;
+        set_window_size(ctx, 300., 220.)?;
         graphics::clear(ctx, graphics::WHITE);
         let fill = DrawMode::fill();
         let mut buttons_mesh = graphics::MeshBuilder::new();
@@ -792,6 +1224,10 @@ impl MainMenu {
             .rectangle(fill, self.custom_width_box, TEXT_BG)
             .rectangle(fill, self.custom_height_box, TEXT_BG)
             .rectangle(fill, self.custom_mines_box, TEXT_BG);
+        buttons_mesh
+            .rectangle(fill, self.first_click_button, BUTTON_BG)
+            .rectangle(fill, self.depth_button, BUTTON_BG)
+            .rectangle(fill, self.placement_button, BUTTON_BG);
         let buttons_mesh = buttons_mesh.build(ctx)?;
         graphics::draw(ctx, &buttons_mesh, graphics::DrawParam::new())?;
         let params = graphics::DrawParam::default().color(graphics::BLACK);
@@ -860,6 +1296,34 @@ impl MainMenu {
                 self.custom_mines_box.top(),
             )),
         )?;
+        let first_click_text = graphics::Text::new(format!("First click: {}", self.first_click));
+        graphics::draw(
+            ctx,
+            &first_click_text,
+            params.dest(Point2::new(
+                self.first_click_button.right(),
+                self.first_click_button.top(),
+            )),
+        )?;
+        let depth_text = graphics::Text::new(format!("Layers: {}", self.depth));
+        graphics::draw(
+            ctx,
+            &depth_text,
+            params.dest(Point2::new(
+                self.depth_button.right(),
+                self.depth_button.top(),
+            )),
+        )?;
+        let placement_text =
+            graphics::Text::new(format!("Custom mines: {}", self.placement.name()));
+        graphics::draw(
+            ctx,
+            &placement_text,
+            params.dest(Point2::new(
+                self.placement_button.right(),
+                self.placement_button.top(),
+            )),
+        )?;
         let confirm_text = graphics::Text::new(self.confirm.to_string());
         graphics::draw(
             ctx,
@@ -901,6 +1365,19 @@ impl MainMenu {
                 self.state = MainMenuState::EditingHeight;
             } else if self.custom_mines_box.contains(p) {
                 self.state = MainMenuState::EditingMines;
+            } else if self.first_click_button.contains(p) {
+                // Each click moves on to the next policy, going back to the first after the last
+                let policies = FirstClickPolicy::ALL;
+                let current = policies.iter().position(|&p| p == self.first_click);
+                self.first_click = policies[current.map_or(0, |i| (i + 1) % policies.len())];
+            } else if self.depth_button.contains(p) {
+                // Each click adds a layer, going back to a flat board after the most
+                self.depth = self.depth % MAX_DEPTH + 1;
+            } else if self.placement_button.contains(p) {
+                let name = self.placement.name();
+                let current = PLACEMENT_NAMES.iter().position(|&n| n == name);
+                let next = PLACEMENT_NAMES[current.map_or(0, |i| (i + 1) % PLACEMENT_NAMES.len())];
+                self.placement = placement_from_name(next).unwrap();
             }
         }
         None
@@ -1047,18 +1524,8 @@ impl MainMenu {
     }
 
     fn validate_custom_data(&mut self) {
-        if self.custom_width > MAX_WIDTH {
-            self.custom_width = MAX_WIDTH;
-        }
-        if self.custom_width < MIN_WIDTH {
-            self.custom_width = MIN_WIDTH;
-        }
-        if self.custom_height > MAX_HEIGHT {
-            self.custom_height = MAX_HEIGHT;
-        }
-        if self.custom_height < MIN_HEIGHT {
-            self.custom_height = MIN_HEIGHT;
-        }
+        self.custom_width = self.custom_width.clamp(MIN_WIDTH, MAX_WIDTH);
+        self.custom_height = self.custom_height.clamp(MIN_HEIGHT, MAX_HEIGHT);
         if self.custom_mines > (self.custom_width - 1) * (self.custom_height - 1) {
             self.custom_mines = (self.custom_width - 1) * (self.custom_height - 1);
         }
@@ -1097,6 +1564,7 @@ fn key_to_number(keycode: &KeyCode) -> Option<usize> {
 #[derive(Debug)]
 struct PopupMenu {
     prompt: &'static str,
+    details: String,
     button_1_prompt: &'static str,
     button_2_prompt: &'static str,
     bounds: Rect,
@@ -1107,23 +1575,27 @@ struct PopupMenu {
 impl PopupMenu {
     fn new(
         prompt: &'static str,
+        details: String,
         button_1_prompt: &'static str,
         button_2_prompt: &'static str,
         x: f32,
         y: f32,
     ) -> PopupMenu {
-        let bounds = Rect::new(x, y, 150., 80.);
-        let button_1_box = Rect::new(x + 5., y + 40., 90., 30.);
-        let button_2_box = Rect::new(x + 105., y + 40., 40., 30.);
-        let m = PopupMenu {
+        // The details go between the prompt and the buttons
+        let details_height = details.lines().count() as f32 * DETAILS_LINE_HEIGHT;
+        let bounds = Rect::new(x, y, 150., 80. + details_height);
+        let button_1_box = Rect::new(x + 5., y + 40. + details_height, 90., 30.);
+        let button_2_box = Rect::new(x + 105., y + 40. + details_height, 40., 30.);
+
+        PopupMenu {
             prompt,
+            details,
             button_1_prompt,
             button_2_prompt,
             bounds,
             button_1_box,
             button_2_box,
-        };
-        m
+        }
     }
 
     fn draw(&self, ctx: &mut Context) -> GameResult {
@@ -1153,6 +1625,15 @@ impl PopupMenu {
             &prompt_text,
             params.dest(Point2::new(self.bounds.x + 30., self.bounds.y + 10.)),
         )?;
+        let details_text = graphics::Text::new(
+            graphics::TextFragment::new(self.details.as_str())
+                .scale(graphics::Scale::uniform(DETAILS_LINE_HEIGHT)),
+        );
+        graphics::draw(
+            ctx,
+            &details_text,
+            params.dest(Point2::new(self.bounds.x + 8., self.bounds.y + 32.)),
+        )?;
         let button_1_text = graphics::Text::new(self.button_1_prompt.to_string());
         graphics::draw(
             ctx,
@@ -1185,6 +1666,185 @@ impl PopupMenu {
     }
 }
 
+/// Plays a replay back on a board, following a clock that can be paused, sped up and moved.
+#[derive(Debug)]
+struct ReplayViewer {
+    replay: Replay,
+    board: Board,
+    /// The number of steps that have been applied to the board
+    position: usize,
+    clock: f64,
+    /// Index into `REPLAY_SPEEDS`
+    speed: usize,
+    playing: bool,
+}
+
+impl ReplayViewer {
+    fn new(replay: Replay) -> Result<ReplayViewer, BoardError> {
+        Ok(ReplayViewer {
+            board: replay.start_board()?,
+            replay,
+            position: 0,
+            clock: 0.,
+            speed: NORMAL_SPEED,
+            playing: true,
+        })
+    }
+
+    /// The time of the last step.
+    fn length(&self) -> f64 {
+        self.replay.steps.last().map_or(0., |step| step.time)
+    }
+
+    fn advance(&mut self, delta: f64) {
+        if !self.playing {
+            return;
+        }
+        self.clock += delta * REPLAY_SPEEDS[self.speed];
+        if self.clock >= self.length() {
+            self.clock = self.length();
+            self.playing = false;
+        }
+        self.apply_steps();
+    }
+
+    /// Moves the clock to `time`. Going backwards plays the replay again from the start.
+    fn seek(&mut self, time: f64) {
+        let time = time.clamp(0., self.length());
+        if time < self.clock {
+            // The board was checked when the viewer was made
+            self.board = self.replay.start_board().unwrap();
+            self.position = 0;
+        }
+        self.clock = time;
+        self.apply_steps();
+    }
+
+    /// Applies every step up to the clock.
+    fn apply_steps(&mut self) {
+        while let Some(step) = self.replay.steps.get(self.position) {
+            if step.time > self.clock {
+                break;
+            }
+            if let Err(message) = step.action.apply(&mut self.board) {
+                eprintln!("{}", message);
+            }
+            self.position += 1;
+        }
+    }
+
+    /// The tile of the last click that has been played, and the colour to mark it with.
+    fn last_click(&self) -> Option<(usize, usize, Color)> {
+        self.replay.steps[..self.position]
+            .iter()
+            .rev()
+            .find_map(|step| match step.action {
+                Action::Reveal(x, y) => Some((x, y, CURSOR_REVEAL)),
+                Action::Toggle(x, y) => Some((x, y, CURSOR_TOGGLE)),
+                Action::Chord(x, y) => Some((x, y, CURSOR_CHORD)),
+                _ => None,
+            })
+    }
+}
+
+/// A game in progress read back from the save file.
+#[derive(Debug)]
+struct SavedGame {
+    diff: DifficultySetting,
+    custom_diff: Difficulty,
+    time: f64,
+    board: Board,
+}
+
+/// Reads a save written by `MinesweeperGame::save_game`, of any version up to `SAVE_VERSION`.
+fn read_save(save: &str) -> Result<SavedGame, String> {
+    let (header, rest) = save.split_once('\n').ok_or("The save file is incomplete")?;
+    let version: u32 = header
+        .strip_prefix(SAVE_HEADER)
+        .and_then(|version| version.trim().parse().ok())
+        .filter(|version| (1..=SAVE_VERSION).contains(version))
+        .ok_or_else(|| format!("Unsupported save file: {}", header))?;
+    // Version 1 has an extra line for the mines left unflagged before the board
+    let settings = if version == 1 { 4 } else { 3 };
+    let mut parts = rest.splitn(settings + 1, '\n');
+    let mut next_line = || parts.next().ok_or("The save file is incomplete");
+    let diff = DifficultySetting::from_name(next_line()?);
+    let custom: Vec<usize> = next_line()?
+        .split(',')
+        .map(|s| {
+            s.trim()
+                .parse()
+                .map_err(|_| format!("Invalid custom setting: {}", s))
+        })
+        .collect::<Result<_, _>>()?;
+    if custom.len() != 3 {
+        return Err("The custom difficulty needs a width, height and mine count".to_owned());
+    }
+    let timer_line = next_line()?;
+    let time: f64 = timer_line
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid timer: {}", timer_line))?;
+    if version == 1 {
+        next_line()?;
+    }
+    let board = Board::load(next_line()?).map_err(|e| e.to_string())?;
+    Ok(SavedGame {
+        diff,
+        custom_diff: Difficulty(custom[0], custom[1], custom[2]),
+        time,
+        board,
+    })
+}
+
+fn create_board(
+    diff: DifficultySetting,
+    custom_diff: &Difficulty,
+    seed: Option<u64>,
+    first_click: FirstClickPolicy,
+    depth: usize,
+    placement: &Rc<dyn MinePlacement>,
+) -> Board {
+    let Difficulty(width, height, mines) = match diff {
+        DifficultySetting::Easy => EASY,
+        DifficultySetting::Medium => MEDIUM,
+        DifficultySetting::Hard => HARD,
+        DifficultySetting::Custom => Difficulty(custom_diff.0, custom_diff.1, custom_diff.2),
+    };
+    // Each layer of a 3D board gets as many mines as the difficulty has
+    let mut builder = BoardBuilder::new(width, height * depth)
+        .mines(mines * depth)
+        .first_click(first_click);
+    if depth > 1 {
+        builder = builder.topology(Rc::new(Cubic::new(depth)));
+    }
+    // The standard difficulties always spread their mines evenly, so their best times stay fair
+    if diff == DifficultySetting::Custom {
+        builder = builder.placement(placement.clone());
+    }
+    if let Some(seed) = seed {
+        builder = builder.seed(seed);
+    }
+    match builder.build() {
+        Ok(board) => {
+            println!("Board seed: {}", board.seed);
+            board
+        }
+        // A hand edited config can hold a custom difficulty the menu would not allow
+        Err(e) => {
+            eprintln!("{}, playing on easy instead", e);
+            create_board(
+                DifficultySetting::Easy,
+                custom_diff,
+                seed,
+                first_click,
+                1,
+                placement,
+            )
+        }
+    }
+}
+
 fn set_window_size(ctx: &mut Context, width: f32, height: f32) -> GameResult {
     graphics::set_drawable_size(ctx, width, height)?;
     graphics::set_screen_coordinates(ctx, Rect::new(0., 0., width, height))?;
@@ -1212,7 +1872,71 @@ pub fn start_game() -> GameResult {
 
     let (ctx, events_loop) = &mut cb.build()?;
 
-    let mut game = MinesweeperGame::new(ctx)?;
+    // An optional board seed can be passed as the first argument to replay a board, or the path
+    // of a replay file to watch
+    let arg = env::args().nth(1);
+    let seed = arg.as_ref().and_then(|arg| arg.parse().ok());
+    let mut game = MinesweeperGame::new(ctx, seed)?;
     println!("{}", game);
+    if let (Some(path), None) = (arg, seed) {
+        let replay = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|save| Replay::load(&save))
+            .map_err(ggez::GameError::ResourceLoadError)?;
+        game.start_replay(ctx, replay)?;
+    }
     event::run(ctx, events_loop, &mut game)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_version_1_saves() {
+        let save = "minesweeper game 1
+custom
+5,4,3
+12.5
+2
+minesweeper board 1
+3,3,1,42,true,false
+1*1
+111
+000
+RHH
+RRR
+RRR
+";
+        let saved = read_save(save).unwrap();
+        assert_eq!(saved.diff, DifficultySetting::Custom);
+        assert_eq!(
+            (
+                saved.custom_diff.0,
+                saved.custom_diff.1,
+                saved.custom_diff.2
+            ),
+            (5, 4, 3)
+        );
+        assert_eq!(saved.time, 12.5);
+        assert_eq!(saved.board.seed, 42);
+        assert_eq!(saved.board.get_tile_at(1, 0), Ok(Tile::Mine));
+        assert_eq!(saved.board.get_display_at(2, 0), Ok(TileDisplay::Hidden));
+        assert!(saved.board.is_started());
+    }
+
+    #[test]
+    fn reads_saves_it_writes() {
+        let board = BoardBuilder::new(9, 9).mines(10).seed(3).build().unwrap();
+        let save = format!(
+            "{} {}\nhard\n24,16,50\n3.25\n{}",
+            SAVE_HEADER,
+            SAVE_VERSION,
+            board.save()
+        );
+        let saved = read_save(&save).unwrap();
+        assert_eq!(saved.diff, DifficultySetting::Hard);
+        assert_eq!(saved.time, 3.25);
+        assert_eq!(saved.board.save(), board.save());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::minesweeper::*;
+use crate::probability::{self, Frontier};
+use crate::solver::View;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// How many tiles the search may try before giving up on finding a layout.
+const SEARCH_BUDGET: usize = 200_000;
+
+/// Finds a layout of the hidden tiles that agrees with every revealed number and the number of
+/// mines, with (x, y) a mine or not as asked. The layout is kept as close to the board's current
+/// one as it can be, with any difference in the number of mines made up at random among the
+/// hidden tiles away from the numbers. Returns every tile that should hold a mine, or None if no
+/// such layout was found.
+pub fn relayout(
+    board: &Board,
+    x: usize,
+    y: usize,
+    mine: bool,
+    rng: &mut impl Rng,
+) -> Option<Vec<(usize, usize)>> {
+    let mut view = View::new(board);
+    view.mark(x, y, mine);
+    let constraints = view.constraints();
+    let mines_left = view.mines_left();
+
+    let frontier = Frontier::new(&constraints.iter().collect::<Vec<_>>());
+    let interior: Vec<(usize, usize)> = view
+        .unknown_cells()
+        .into_iter()
+        .filter(|cell| !frontier.contains(cell))
+        .collect();
+
+    let is_mine = |&(cx, cy): &(usize, usize)| board.get_tile_at(cx, cy) == Ok(Tile::Mine);
+    let mut search = Search {
+        preferred: frontier.cells.iter().map(is_mine).collect(),
+        frontier,
+        max_mines: mines_left,
+        interior: interior.len(),
+        mines: 0,
+        steps: 0,
+    };
+    if !search.search(0, 0) {
+        return None;
+    }
+
+    let mut mines: Vec<(usize, usize)> = search
+        .frontier
+        .cells
+        .iter()
+        .zip(search.frontier.assignment.iter())
+        .filter(|(_, &mine)| mine)
+        .map(|(cell, _)| *cell)
+        .collect();
+    if mine {
+        mines.push((x, y));
+    }
+    // Keep the mines already away from the numbers, adding or taking away what the frontier
+    // doesn't account for
+    let wanted = mines_left - search.mines;
+    let (mut kept, mut free): (Vec<_>, Vec<_>) = interior.into_iter().partition(is_mine);
+    kept.shuffle(rng);
+    free.shuffle(rng);
+    kept.truncate(wanted);
+    let missing = wanted - kept.len();
+    kept.extend(free.into_iter().take(missing));
+    mines.extend(kept);
+    Some(mines)
+}
+
+/// Backtracking search for one arrangement of mines on the frontier that satisfies every
+/// constraint, trying each tile's current state first.
+struct Search {
+    frontier: Frontier,
+    preferred: Vec<bool>,
+    /// The mines not yet known to be anywhere
+    max_mines: usize,
+    /// The number of hidden tiles away from the numbers, which can take any mines left over
+    interior: usize,
+    /// The number of mines in the arrangement found
+    mines: usize,
+    steps: usize,
+}
+
+impl Search {
+    fn search(&mut self, cell: usize, mines: usize) -> bool {
+        if cell == self.frontier.cells.len() {
+            // The rest of the mines have to fit away from the numbers
+            self.mines = mines;
+            return self.max_mines - mines <= self.interior;
+        }
+        self.steps += 1;
+        if self.steps > SEARCH_BUDGET {
+            return false;
+        }
+        let preferred = self.preferred[cell];
+        for mine in [preferred, !preferred] {
+            let added = mine as usize;
+            if mines + added > self.max_mines || !self.frontier.assign(cell, mine) {
+                continue;
+            }
+            if self.search(cell + 1, mines + added) {
+                return true;
+            }
+            self.frontier.unassign(cell);
+        }
+        false
+    }
+}
+
+/// Returns true if any hidden tile other than (x, y) is certain to be safe from what has been
+/// revealed, along with the chance that (x, y) itself is a mine.
+pub fn safe_move(board: &Board, x: usize, y: usize) -> (bool, f64) {
+    let probabilities = probability::mine_probabilities(board);
+    let mut safe_elsewhere = false;
+    for (cx, column) in probabilities.iter().enumerate() {
+        for (cy, probability) in column.iter().enumerate() {
+            if (cx, cy) != (x, y) && *probability == Some(0.) {
+                safe_elsewhere = true;
+            }
+        }
+    }
+    let here = probabilities[x][y].unwrap_or(0.);
+    (safe_elsewhere, here)
+}
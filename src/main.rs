@@ -1,6 +1,13 @@
 mod game2d;
+mod kind;
 mod minesweeper;
+mod placement;
+mod probability;
+mod replay;
+mod shape;
+mod solver;
 mod textgame;
+mod topology;
 
 use std::io;
 
@@ -11,7 +18,7 @@ fn main() {
         Ok(_) => std::process::exit(0),
         Err(message) => println!("Game ended with an error message: {}", message),
     };
-    println!("Enter the kind of game to run(console or 2d): ");
+    println!("Enter the kind of game to run(console, replay or 2d): ");
     let mut input = String::new();
     io::stdin()
         .read_line(&mut input)
@@ -20,9 +27,11 @@ fn main() {
     if input == "console" {
         let mut game = TextGame::new();
         game.main_loop();
+    } else if input == "replay" {
+        textgame::play_replay();
     } else if input == "2d" {
         //
     } else {
-        println!("You must enter either console or 2d.");
+        println!("You must enter console, replay or 2d.");
     }
 }
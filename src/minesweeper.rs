@@ -1,89 +1,45 @@
-use rand;
-use rand::Rng;
+use crate::kind;
+use crate::placement::{placement_from_name, MinePlacement, Uniform};
+use crate::shape::Shape;
+use crate::solver;
+use crate::topology::{topology_from_name, Square, Topology};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
 use std::fmt::{Display, Formatter};
+use std::rc::Rc;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
-pub enum Digit {
-    Zero,
-    One,
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-}
-
-impl Digit {
-    pub fn to_int(d: Digit) -> i32 {
-        use Digit::*;
-        match d {
-            Zero => 0,
-            One => 1,
-            Two => 2,
-            Three => 3,
-            Four => 4,
-            Five => 5,
-            Six => 6,
-            Seven => 7,
-            Eight => 8,
-            Nine => 9,
-        }
-    }
-
-    pub fn from_int<T: Into<usize>>(i: T) -> Digit {
-        use Digit::*;
-        let i: usize = i.into();
-        match i {
-            0 => Zero,
-            1 => One,
-            2 => Two,
-            3 => Three,
-            4 => Four,
-            5 => Five,
-            6 => Six,
-            7 => Seven,
-            8 => Eight,
-            9 => Nine,
-            _ => panic!("Digits can only be created from a single digit number"),
-        }
-    }
-}
-
-impl Display for Digit {
+/// The number of mines touching a safe tile. Neighbourhoods can be any size, so this isn't
+/// limited to a single digit.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct Count(pub usize);
+
+impl Count {
+    pub const ZERO: Count = Count(0);
+}
+
+impl Display for Count {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        use Digit::*;
-        match self {
-            Zero => write!(f, "{}", 0),
-            One => write!(f, "{}", 1),
-            Two => write!(f, "{}", 2),
-            Three => write!(f, "{}", 3),
-            Four => write!(f, "{}", 4),
-            Five => write!(f, "{}", 5),
-            Six => write!(f, "{}", 6),
-            Seven => write!(f, "{}", 7),
-            Eight => write!(f, "{}", 8),
-            Nine => write!(f, "{}", 9),
-        }
+        write!(f, "{}", self.0)
     }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Tile {
-    Safe(Digit),
+    Safe(Count),
     Mine,
+    /// Not part of the board's shape
+    Void,
 }
 
 impl Display for Tile {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        use Digit::*;
         use Tile::*;
         match self {
-            Safe(Zero) => write!(f, "|_|"),
-            Safe(other) => write!(f, "|{}|", other),
-            Mine => write!(f, "|*|"),
+            Safe(Count::ZERO) => write!(f, "_"),
+            Safe(count) => write!(f, "{}", count),
+            Mine => write!(f, "*"),
+            Void => write!(f, " "),
         }
     }
 }
@@ -96,448 +52,2449 @@ pub enum TileDisplay {
     Question,
 }
 
-#[derive(Debug)]
-pub struct Board {
-    tiles: Vec<Vec<Tile>>,
-    display: Vec<Vec<TileDisplay>>,
-    pub width: usize,
-    pub height: usize,
-    pub mines: usize,
-    any_revealed: bool,
+/// Version of the text format written by `Board::save`. Version 1 saves are all square boards,
+/// version 2 saves never wrap, version 3 saves have no counts above 9, version 4 saves are all
+/// rectangles, version 5 saves only record whether the board had to be solvable without
+/// guessing and version 6 saves all spread their mines evenly.
+const SAVE_VERSION: u32 = 7;
+const SAVE_HEADER: &str = "minesweeper board";
+
+/// How many layouts to try before giving up on finding one that can be solved without guessing.
+const NO_GUESS_ATTEMPTS: usize = 1000;
+
+/// The most mines a no guess board can have, as a percentage of its tiles. Any denser and large
+/// boards often run out of attempts before finding a layout that can be solved without guessing.
+const NO_GUESS_PERCENT: usize = 22;
+
+/// Everything that can go wrong when making or playing a board.
+#[derive(Debug, PartialEq, Clone)]
+pub enum BoardError {
+    OutOfBounds {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    /// A board needs at least one row and one column
+    EmptyBoard {
+        width: usize,
+        height: usize,
+    },
+    /// There are more tiles than can be counted
+    TooLarge {
+        width: usize,
+        height: usize,
+    },
+    /// The first click policy needs more safe tiles than the board would have
+    TooManyMines {
+        mines: usize,
+        max: usize,
+        first_click: FirstClickPolicy,
+    },
+    /// Mine density has to be at least 0 and less than 1
+    InvalidDensity(f64),
+    /// The placement lays mines in pairs, and no tile is sure to be free for the odd one out
+    UnpairedMine {
+        mines: usize,
+        placement: String,
+    },
+    /// A kind board moves its mines as if they were spread evenly, so it can't keep to any other
+    /// placement
+    KindPlacement {
+        placement: String,
+    },
+    /// None of the layouts tried could be cleared without guessing
+    NoGuessLayout {
+        mines: usize,
+        attempts: usize,
+    },
+    AlreadyRevealed {
+        x: usize,
+        y: usize,
+    },
+    /// The tile is inside the board's rectangle but not part of its shape
+    OutsideShape {
+        x: usize,
+        y: usize,
+    },
+    /// Only a revealed tile can have the tiles around it revealed
+    NotRevealed {
+        x: usize,
+        y: usize,
+    },
+    /// The game has already been won or lost
+    GameOver,
+    /// A tile can only be chorded when it has as many flags around it as its number
+    WrongFlagCount {
+        x: usize,
+        y: usize,
+        flags: usize,
+        number: usize,
+    },
+    /// The rows of the board can't be split evenly between its layers
+    UnevenLayers {
+        height: usize,
+        layers: usize,
+    },
+    /// Tiles on opposite edges of a wrapped board wouldn't fit together
+    CannotWrap {
+        width: usize,
+        height: usize,
+        topology: String,
+    },
+    /// A saved board couldn't be read
+    InvalidSave(String),
 }
 
-impl Board {
-    pub fn new(width: usize, height: usize, mines: usize) -> Board {
-        let display: Vec<Vec<TileDisplay>> = vec![vec![TileDisplay::Hidden; height]; width];
-        let mut tiles: Vec<Vec<Tile>> = vec![vec![Tile::Safe(Digit::Zero); height]; width];
-        let mut num_mines = 0;
-        let mut rng = rand::thread_rng();
-        while num_mines < mines {
-            let x = rng.gen_range(0, width);
-            let y = rng.gen_range(0, height);
-            if tiles[x][y] == Tile::Mine {
-                continue;
-            } else {
-                tiles[x][y] = Tile::Mine;
-                num_mines += 1;
+impl Display for BoardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use BoardError::*;
+        match self {
+            OutOfBounds {
+                x,
+                y,
+                width,
+                height,
+            } => write!(
+                f,
+                "({}, {}) is off the board, which is {} wide and {} high",
+                x, y, width, height
+            ),
+            EmptyBoard { width, height } => {
+                write!(f, "A {}x{} board has no tiles", width, height)
             }
+            TooLarge { width, height } => write!(f, "A {}x{} board is too large", width, height),
+            TooManyMines {
+                mines,
+                max,
+                first_click,
+            } => write!(
+                f,
+                "The board can't have {} mines, it has room for at most {} with the {} first \
+                 click policy",
+                mines, max, first_click
+            ),
+            InvalidDensity(density) => {
+                write!(f, "A mine density of {} is not between 0 and 1", density)
+            }
+            UnpairedMine { mines, placement } => write!(
+                f,
+                "{} mines can't all be paired up on a {} board, as no tile is sure to be left \
+                 for the odd one",
+                mines, placement
+            ),
+            KindPlacement { placement } => write!(
+                f,
+                "The kind first click policy can't keep the mines of a {} board in place",
+                placement
+            ),
+            NoGuessLayout { mines, attempts } => write!(
+                f,
+                "None of {} layouts of {} mines could be cleared without guessing",
+                attempts, mines
+            ),
+            AlreadyRevealed { x, y } => write!(f, "({}, {}) is already revealed", x, y),
+            OutsideShape { x, y } => write!(f, "({}, {}) is not part of the board", x, y),
+            NotRevealed { x, y } => write!(f, "({}, {}) hasn't been revealed", x, y),
+            GameOver => write!(f, "The game is over"),
+            WrongFlagCount {
+                x,
+                y,
+                flags,
+                number,
+            } => write!(
+                f,
+                "({}, {}) is a {} but has {} flags around it",
+                x, y, number, flags
+            ),
+            UnevenLayers { height, layers } => write!(
+                f,
+                "A board {} rows high can't be split into {} layers",
+                height, layers
+            ),
+            CannotWrap {
+                width,
+                height,
+                topology,
+            } => write!(
+                f,
+                "A {}x{} {} board can't wrap around",
+                width, height, topology
+            ),
+            InvalidSave(message) => write!(f, "{}", message),
         }
-        Self::update_digits(&mut tiles, height, width);
+    }
+}
 
-        Board {
-            tiles,
-            display,
+impl std::error::Error for BoardError {}
+
+/// What is done to the layout on the first reveal so that the game doesn't end straight away, and
+/// on a kind board on every reveal after.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum FirstClickPolicy {
+    /// Nothing is moved, so the first tile revealed can be a mine
+    Unprotected,
+    /// A mine on the first tile revealed is moved, but the tiles around it are left alone
+    SafeOnly,
+    /// Mines are moved off the first tile revealed and the tiles around it, so it opens up
+    Opening,
+    /// Like `Opening`, and the layout is chosen so the rest of the board can be cleared without
+    /// guessing
+    NoGuess,
+    /// Like `Opening`, and the hidden tiles aren't settled until they are revealed. A guess is
+    /// always safe when nothing could be proven safe, and always a mine when something could.
+    /// Only a uniform placement can be kind
+    Kind,
+}
+
+impl FirstClickPolicy {
+    /// Every policy, from the least to the most help.
+    pub const ALL: [FirstClickPolicy; 5] = [
+        FirstClickPolicy::Unprotected,
+        FirstClickPolicy::SafeOnly,
+        FirstClickPolicy::Opening,
+        FirstClickPolicy::NoGuess,
+        FirstClickPolicy::Kind,
+    ];
+
+    /// Returns the policy written as `name` by its `Display` impl.
+    pub fn from_name(name: &str) -> Option<FirstClickPolicy> {
+        FirstClickPolicy::ALL
+            .iter()
+            .find(|policy| policy.to_string() == name)
+            .copied()
+    }
+
+    /// The number of tiles that have to be kept clear of mines when the first tile is revealed,
+    /// wherever it is.
+    fn cleared_tiles(&self, board: &BoardBuilder) -> usize {
+        match self {
+            // Even when the first tile could be a mine there has to be a safe tile to find
+            FirstClickPolicy::Unprotected | FirstClickPolicy::SafeOnly => 1,
+            FirstClickPolicy::Opening | FirstClickPolicy::NoGuess | FirstClickPolicy::Kind => {
+                board
+                    .topology
+                    .most_neighbours(board.width, board.height, board.wrap)
+                    + 1
+            }
+        }
+    }
+}
+
+impl Display for FirstClickPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use FirstClickPolicy::*;
+        match self {
+            Unprotected => write!(f, "unprotected"),
+            SafeOnly => write!(f, "safe"),
+            Opening => write!(f, "opening"),
+            NoGuess => write!(f, "no-guess"),
+            Kind => write!(f, "kind"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum MineCount {
+    Exactly(usize),
+    Density(f64),
+}
+
+/// Settings for a new board. Nothing is checked until `BoardBuilder::build`, which reports any
+/// settings that can't make a playable board.
+#[derive(Debug, Clone)]
+pub struct BoardBuilder {
+    width: usize,
+    height: usize,
+    mines: MineCount,
+    seed: Option<u64>,
+    first_click: FirstClickPolicy,
+    topology: Rc<dyn Topology>,
+    wrap: bool,
+    shape: Option<Shape>,
+    placement: Rc<dyn MinePlacement>,
+}
+
+impl BoardBuilder {
+    /// Starts a board of square tiles of the given size with no mines, a random seed and an
+    /// opening on the first click. Its edges don't wrap and its mines are spread evenly.
+    pub fn new(width: usize, height: usize) -> BoardBuilder {
+        BoardBuilder {
             width,
             height,
-            mines,
-            any_revealed: false,
+            mines: MineCount::Exactly(0),
+            seed: None,
+            first_click: FirstClickPolicy::Opening,
+            topology: Rc::new(Square),
+            wrap: false,
+            shape: None,
+            placement: Rc::new(Uniform),
         }
     }
 
-    fn update_digits(tiles: &mut Vec<Vec<Tile>>, height: usize, width: usize) {
-        let mut counts: Vec<Vec<usize>> = vec![vec![0; height]; width];
-        // Count Mines Adjacent to each safe tile
-        for x in 0..width {
-            for y in 0..height {
-                if tiles[x][y] == Tile::Mine {
-                    // We don't care how many mines are adjacent to a mine
-                    continue;
-                };
-                if y > 0 {
-                    // Check Up
-                    counts[x][y] += match tiles[x][y - 1] {
-                        Tile::Mine => 1,
-                        _ => 0,
-                    };
-                }
-                if y < height - 1 {
-                    // Check Down
-                    counts[x][y] += match tiles[x][y + 1] {
-                        Tile::Mine => 1,
-                        _ => 0,
-                    };
-                }
-                if x < width - 1 && y < height - 1 {
-                    // Check Down-Right
-                    counts[x][y] += match tiles[x + 1][y + 1] {
-                        Tile::Mine => 1,
-                        _ => 0,
-                    };
-                }
-                if x < width - 1 {
-                    // Check Right
-                    counts[x][y] += match tiles[x + 1][y] {
-                        Tile::Mine => 1,
-                        _ => 0,
-                    };
-                }
-                if x < width - 1 && y > 0 {
-                    // Check Up-Right
-                    counts[x][y] += match tiles[x + 1][y - 1] {
-                        Tile::Mine => 1,
-                        _ => 0,
-                    };
-                }
-                if x > 0 && y < height - 1 {
-                    // Check Down-Left
-                    counts[x][y] += match tiles[x - 1][y + 1] {
-                        Tile::Mine => 1,
-                        _ => 0,
-                    };
-                }
-                if x > 0 {
-                    // Check Left
-                    counts[x][y] += match tiles[x - 1][y] {
-                        Tile::Mine => 1,
-                        _ => 0,
-                    };
-                }
-                if x > 0 && y > 0 {
-                    // Check Up-Left
-                    counts[x][y] += match tiles[x - 1][y - 1] {
-                        Tile::Mine => 1,
-                        _ => 0,
-                    };
-                }
-            }
+    pub fn mines(mut self, mines: usize) -> BoardBuilder {
+        self.mines = MineCount::Exactly(mines);
+        self
+    }
+
+    /// Sets the number of mines to a fraction of the tiles, rounded to the nearest mine.
+    pub fn density(mut self, density: f64) -> BoardBuilder {
+        self.mines = MineCount::Density(density);
+        self
+    }
+
+    /// Makes the layout depend only on the seed and the other settings. Without one a random
+    /// seed is used, which is kept in `Board::seed`.
+    pub fn seed(mut self, seed: u64) -> BoardBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn first_click(mut self, first_click: FirstClickPolicy) -> BoardBuilder {
+        self.first_click = first_click;
+        self
+    }
+
+    pub fn topology(mut self, topology: Rc<dyn Topology>) -> BoardBuilder {
+        self.topology = topology;
+        self
+    }
+
+    /// Makes tiles on each edge of the board touch the tiles on the opposite edge.
+    pub fn wrap(mut self, wrap: bool) -> BoardBuilder {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Leaves every tile outside the shape off the board. The board takes the shape's width and
+    /// height.
+    pub fn shape(mut self, shape: Shape) -> BoardBuilder {
+        self.width = shape.width();
+        self.height = shape.height();
+        self.shape = Some(shape);
+        self
+    }
+
+    pub fn placement(mut self, placement: Rc<dyn MinePlacement>) -> BoardBuilder {
+        self.placement = placement;
+        self
+    }
+
+    fn in_shape(&self, x: usize, y: usize) -> bool {
+        self.shape.as_ref().is_none_or(|shape| shape.contains(x, y))
+    }
+
+    /// The number of tiles on the board that are part of its shape.
+    fn tiles(&self) -> usize {
+        match &self.shape {
+            Some(shape) => shape.cells(),
+            None => self.width.saturating_mul(self.height),
         }
-        // Update the tiles to reflect the counts
-        for x in 0..width {
-            for y in 0..height {
-                if tiles[x][y] != Tile::Mine {
-                    tiles[x][y] = Tile::Safe(Digit::from_int(counts[x][y]));
-                }
-            }
+    }
+
+    /// The most mines a board of this size can have under the first click policy and placement.
+    pub fn max_mines(&self) -> usize {
+        if self.width == 0 || self.height == 0 {
+            return 0;
+        }
+        let (singles, pairs) = count_groups(&*self.placement, self.width, self.height, |x, y| {
+            self.in_shape(x, y)
+        });
+        let mut cleared = self.first_click.cleared_tiles(self);
+        // Mines moved off the first click take their partners with them, so the tiles opposite
+        // are cleared too
+        if pairs > 0 {
+            cleared *= 2;
+        }
+        let max = (singles + 2 * pairs).saturating_sub(cleared);
+        if self.first_click == FirstClickPolicy::NoGuess {
+            max.min(self.tiles().saturating_mul(NO_GUESS_PERCENT) / 100)
+        } else {
+            max
         }
     }
 
-    pub fn reveal_all(&mut self) {
-        for row in self.display.iter_mut() {
-            for slot in row.iter_mut() {
-                *slot = TileDisplay::Revealed;
-            }
+    /// Checks that a placement that lays mines in pairs will have a tile free for an odd mine out,
+    /// wherever the first click is.
+    fn check_pairs(&self, mines: usize) -> Result<(), BoardError> {
+        let (singles, pairs) = count_groups(&*self.placement, self.width, self.height, |x, y| {
+            self.in_shape(x, y)
+        });
+        if mines.is_multiple_of(2) || pairs == 0 || singles > self.most_cleared_singles() {
+            Ok(())
+        } else {
+            Err(BoardError::UnpairedMine {
+                mines,
+                placement: self.placement.name(),
+            })
         }
     }
 
-    pub fn reveal_at(&mut self, x: usize, y: usize) -> Result<Tile, &'static str> {
-        if x >= self.width || y >= self.height {
-            return Err("x and y must be less than width and height");
+    /// Checks that a kind board spreads its mines evenly, as that is all it keeps to when it settles
+    /// its tiles.
+    fn check_kind(&self) -> Result<(), BoardError> {
+        if self.first_click == FirstClickPolicy::Kind && !self.placement.is_uniform() {
+            Err(BoardError::KindPlacement {
+                placement: self.placement.name(),
+            })
         } else {
-            if !self.any_revealed {
-                self.guarantee_zero(x, y);
-            }
-            self.any_revealed = true;
-            self.display[x][y] = TileDisplay::Revealed;
-            if self.tiles[x][y] == Tile::Safe(Digit::Zero) {
-                self.reveal_adjacent(x, y).unwrap();
+            Ok(())
+        }
+    }
+
+    /// The number of tiles that are their own partner to leave without mines when laying them.
+    /// However many mines the first click clears from those tiles, there is then always one left
+    /// elsewhere for an odd mine out. A placement that doesn't pair mines keeps none.
+    fn kept_singles(&self) -> usize {
+        let (_, pairs) = count_groups(&*self.placement, self.width, self.height, |x, y| {
+            self.in_shape(x, y)
+        });
+        if pairs == 0 {
+            0
+        } else {
+            self.most_cleared_singles()
+        }
+    }
+
+    /// The most tiles that are their own partner the first click could clear, wherever it is.
+    fn most_cleared_singles(&self) -> usize {
+        let (width, height) = (self.width, self.height);
+        // Tiles that are their own partner are the only ones that can take an odd mine
+        let single = |&(x, y): &(usize, usize)| {
+            self.in_shape(x, y) && self.placement.partner(x, y, width, height).is_none()
+        };
+        match self.first_click {
+            FirstClickPolicy::Unprotected => 0,
+            FirstClickPolicy::SafeOnly => 1,
+            FirstClickPolicy::Opening | FirstClickPolicy::NoGuess | FirstClickPolicy::Kind => {
+                let tiles = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)));
+                tiles
+                    .filter(single)
+                    .map(|(x, y)| {
+                        let around = self.topology.neighbours(x, y, width, height, self.wrap);
+                        1 + around.iter().filter(|tile| single(tile)).count()
+                    })
+                    .max()
+                    .unwrap_or(0)
             }
-            return Ok(self.tiles[x][y]);
         }
     }
 
-    fn guarantee_zero(&mut self, x: usize, y: usize) {
-        //Should be called the first time a tile is revealed
-        //Moves any mines from (x, y) or adjacent to somewhere else at random
-        assert!(x < self.width && y < self.height);
-        let mut removed_mines = 0;
+    pub fn build(&self) -> Result<Board, BoardError> {
+        let seed = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let mut board = self.build_with_rng(StdRng::seed_from_u64(seed))?;
+        board.seed = seed;
+        Ok(board)
+    }
 
-        // Check x, y and all adjacent tiles
-        // If any is a Mine set it to a Safe(Zero) temporarily and increment removed_mines
-        if self.tiles[x][y] == Tile::Mine {
-            self.tiles[x][y] = Tile::Safe(Digit::Zero);
-            removed_mines += 1;
+    /// Like `build`, but lays out the mines with the given rng and keeps using it to relocate
+    /// mines on the first reveal, so the caller decides every random choice the board makes. The
+    /// board's seed is left at 0, as no seed can recreate the layout.
+    pub fn build_with_rng(&self, rng: StdRng) -> Result<Board, BoardError> {
+        let (width, height) = (self.width, self.height);
+        if width == 0 || height == 0 {
+            return Err(BoardError::EmptyBoard { width, height });
         }
-        if x > 0 {
-            // Check Left
-            if self.tiles[x - 1][y] == Tile::Mine {
-                self.tiles[x - 1][y] = Tile::Safe(Digit::Zero);
-                removed_mines += 1;
-            }
-            // Check Up-Left
-            if y > 0 {
-                if self.tiles[x - 1][y - 1] == Tile::Mine {
-                    self.tiles[x - 1][y - 1] = Tile::Safe(Digit::Zero);
-                    removed_mines += 1;
-                }
-            }
-            // Check Down-Left
-            if y < self.height - 1 {
-                if self.tiles[x - 1][y + 1] == Tile::Mine {
-                    self.tiles[x - 1][y + 1] = Tile::Safe(Digit::Zero);
-                    removed_mines += 1;
-                }
-            }
+        width
+            .checked_mul(height)
+            .ok_or(BoardError::TooLarge { width, height })?;
+        let tiles = self.tiles();
+        if tiles == 0 {
+            return Err(BoardError::EmptyBoard { width, height });
         }
-        if x < self.width - 1 {
-            // Check Right
-            if self.tiles[x + 1][y] == Tile::Mine {
-                self.tiles[x + 1][y] = Tile::Safe(Digit::Zero);
-                removed_mines += 1;
-            }
-            // Check Up-Right
-            if y > 0 {
-                if self.tiles[x + 1][y - 1] == Tile::Mine {
-                    self.tiles[x + 1][y - 1] = Tile::Safe(Digit::Zero);
-                    removed_mines += 1;
-                }
-            }
-            // Check Down-Right
-            if y < self.height - 1 {
-                if self.tiles[x + 1][y + 1] == Tile::Mine {
-                    self.tiles[x + 1][y + 1] = Tile::Safe(Digit::Zero);
-                    removed_mines += 1;
+        let layers = self.topology.layers();
+        if !height.is_multiple_of(layers) {
+            return Err(BoardError::UnevenLayers { height, layers });
+        }
+        if self.wrap && !self.topology.can_wrap(width, height) {
+            return Err(BoardError::CannotWrap {
+                width,
+                height,
+                topology: self.topology.name(),
+            });
+        }
+        let mines = match self.mines {
+            MineCount::Exactly(mines) => mines,
+            MineCount::Density(density) => {
+                if !(0. ..1.).contains(&density) {
+                    return Err(BoardError::InvalidDensity(density));
                 }
+                (density * tiles as f64).round() as usize
             }
+        };
+        let max = self.max_mines();
+        if mines > max {
+            return Err(BoardError::TooManyMines {
+                mines,
+                max,
+                first_click: self.first_click,
+            });
         }
-        if y > 0 {
-            // Check Up
-            if self.tiles[x][y - 1] == Tile::Mine {
-                self.tiles[x][y - 1] = Tile::Safe(Digit::Zero);
-                removed_mines += 1;
-            }
+        self.check_pairs(mines)?;
+        self.check_kind()?;
+        Ok(Board::generate(self, mines, 0, rng))
+    }
+}
+
+/// What a call that reveals tiles did to the board.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RevealOutcome {
+    /// Every tile that was revealed by the call, in the order they were revealed
+    pub revealed: Vec<(usize, usize)>,
+    /// Every mine that was revealed by the call
+    pub exploded: Vec<(usize, usize)>,
+    /// Whether every safe tile is now revealed
+    pub won: bool,
+}
+
+impl RevealOutcome {
+    pub fn hit_mine(&self) -> bool {
+        !self.exploded.is_empty()
+    }
+}
+
+/// Something that happened on the board, queued for whoever wants to follow the game.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum BoardEvent {
+    /// The first tile was revealed and the layout is now settled
+    GameStarted,
+    CellRevealed {
+        x: usize,
+        y: usize,
+    },
+    /// Revealing a zero at (x, y) opened up `cells` tiles in all, counting itself
+    OpeningCascaded {
+        x: usize,
+        y: usize,
+        cells: usize,
+    },
+    FlagPlaced {
+        x: usize,
+        y: usize,
+    },
+    FlagRemoved {
+        x: usize,
+        y: usize,
+    },
+    MineExploded {
+        x: usize,
+        y: usize,
+    },
+    GameWon,
+    MoveUndone,
+    MoveRedone,
+}
+
+/// Everything a single call on the board changed, kept so that it can be undone and redone.
+#[derive(Debug, Clone, Default)]
+struct Move {
+    /// Each tile whose display changed, with its display before and after
+    display: Vec<(usize, usize, TileDisplay, TileDisplay)>,
+    /// Whether the move was the first reveal, which started the game
+    started: bool,
+    layout: Option<LayoutChange>,
+}
+
+/// The layout before and after a move changed it, by moving mines away from the first reveal or
+/// by settling hidden tiles on a kind board.
+#[derive(Debug, Clone)]
+struct LayoutChange {
+    tiles_before: Vec<Tile>,
+    rng_before: StdRng,
+    tiles_after: Vec<Tile>,
+    rng_after: StdRng,
+}
+
+#[derive(Debug, Clone)]
+pub struct Board {
+    /// Tiles and displays are stored row by row, at `Board::index`. On a kind board the hidden
+    /// tiles are only one layout that fits what has been revealed, and can change before each
+    /// reveal.
+    tiles: Vec<Tile>,
+    display: Vec<TileDisplay>,
+    pub width: usize,
+    pub height: usize,
+    pub mines: usize,
+    pub seed: u64,
+    topology: Rc<dyn Topology>,
+    wrap: bool,
+    placement: Rc<dyn MinePlacement>,
+    /// How many tiles that are their own partner are left without mines when laying them, from
+    /// `BoardBuilder::kept_singles`
+    kept_singles: usize,
+    any_revealed: bool,
+    first_click: FirstClickPolicy,
+    /// The number of safe tiles that aren't revealed yet, so a win can be spotted without a scan
+    hidden_safe: usize,
+    /// The number of mines revealed, so a loss can be spotted without a scan
+    revealed_mines: usize,
+    rng: StdRng,
+    history: Vec<Move>,
+    undone: Vec<Move>,
+    current: Move,
+    /// Events that haven't been taken by `Board::drain_events` yet
+    events: Vec<BoardEvent>,
+}
+
+impl Board {
+    /// Lays out a board whose layout depends only on the rng, the number of mines and the other
+    /// settings. The same rng is later used to relocate mines on the first reveal, so the same
+    /// first click always produces the same board. The settings must already have been checked.
+    fn generate(settings: &BoardBuilder, mines: usize, seed: u64, rng: StdRng) -> Board {
+        let (width, height) = (settings.width, settings.height);
+        let display = vec![TileDisplay::Hidden; width * height];
+        let tiles = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| match &settings.shape {
+                Some(shape) if !shape.contains(x, y) => Tile::Void,
+                _ => Tile::Safe(Count::ZERO),
+            })
+            .collect();
+        let mut board = Board {
+            tiles,
+            display,
+            width,
+            height,
+            mines,
+            seed,
+            topology: settings.topology.clone(),
+            wrap: settings.wrap,
+            placement: settings.placement.clone(),
+            kept_singles: settings.kept_singles(),
+            any_revealed: false,
+            first_click: settings.first_click,
+            hidden_safe: 0,
+            revealed_mines: 0,
+            rng,
+            history: Vec::new(),
+            undone: Vec::new(),
+            current: Move::default(),
+            events: Vec::new(),
+        };
+        board.lay_mines();
+        board
+    }
+
+    /// Builds a started board from rows of tiles, where `*` is a hidden mine, `#` is a hidden
+    /// safe tile and `.` is a revealed one. For tests of code that reads the board.
+    #[cfg(test)]
+    pub fn from_rows(rows: &[&str]) -> Board {
+        let cells = |c: char| {
+            rows.iter().enumerate().flat_map(move |(y, row)| {
+                row.chars()
+                    .enumerate()
+                    .filter(move |&(_, tile)| tile == c)
+                    .map(move |(x, _)| (x, y))
+            })
+        };
+        let mines: Vec<(usize, usize)> = cells('*').collect();
+        let mut board = BoardBuilder::new(rows[0].len(), rows.len())
+            .mines(mines.len())
+            .first_click(FirstClickPolicy::Unprotected)
+            .build()
+            .unwrap();
+        board.set_mines(&mines);
+        for (x, y) in cells('.') {
+            let i = board.index(x, y);
+            board.display[i] = TileDisplay::Revealed;
         }
-        if y < self.height - 1 {
-            // Check Down
-            if self.tiles[x][y + 1] == Tile::Mine {
-                self.tiles[x][y + 1] = Tile::Safe(Digit::Zero);
-                removed_mines += 1;
+        board.any_revealed = true;
+        board.count_tiles();
+        board
+    }
+
+    pub fn first_click(&self) -> FirstClickPolicy {
+        self.first_click
+    }
+
+    /// The most neighbours any tile on the board could have.
+    pub fn most_neighbours(&self) -> usize {
+        self.topology
+            .most_neighbours(self.width, self.height, self.wrap)
+    }
+
+    /// The number of layers the board is split into. Everything but a 3D board has one.
+    pub fn depth(&self) -> usize {
+        self.topology.layers()
+    }
+
+    /// The number of rows in each layer. Row `y` of layer `z` is at `z * layer_height() + y`.
+    pub fn layer_height(&self) -> usize {
+        self.height / self.depth()
+    }
+
+    /// Returns layer `z` of the board, which can be printed on its own.
+    pub fn layer(&self, z: usize) -> Layer<'_> {
+        Layer { board: self, z }
+    }
+
+    /// Returns true once the first tile has been revealed.
+    pub fn is_started(&self) -> bool {
+        self.any_revealed
+    }
+
+    /// Writes the board to a versioned text format that can be read back with `Board::load`.
+    /// The first line is a header with the version, the second holds the settings and the rest
+    /// are one line of tiles per row followed by one line of displays per row. Tiles are
+    /// separated by spaces, as counts can have more than one digit.
+    /// Undo history is not saved.
+    pub fn save(&self) -> String {
+        let mut save = format!("{} {}\n", SAVE_HEADER, SAVE_VERSION);
+        save.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            self.width,
+            self.height,
+            self.mines,
+            self.seed,
+            self.any_revealed,
+            self.first_click,
+            self.topology.name(),
+            self.wrap,
+            self.placement.name()
+        ));
+        for y in 0..self.height {
+            let row: Vec<String> = (0..self.width)
+                .map(|x| match self.tiles[self.index(x, y)] {
+                    Tile::Mine => "*".to_owned(),
+                    Tile::Safe(Count(count)) => count.to_string(),
+                    Tile::Void => "-".to_owned(),
+                })
+                .collect();
+            save.push_str(&row.join(" "));
+            save.push('\n');
+        }
+        for y in 0..self.height {
+            for x in 0..self.width {
+                save.push(match self.display[self.index(x, y)] {
+                    TileDisplay::Hidden => 'H',
+                    TileDisplay::Revealed => 'R',
+                    TileDisplay::Flag => 'F',
+                    TileDisplay::Question => '?',
+                });
             }
+            save.push('\n');
         }
-        assert!(removed_mines <= self.mines);
-        // Reinsert the removed mines at random locations that aren't adjacent to or at x, y
-        let x_range: (usize, usize, usize, usize);
-        if x > 0 {
-            x_range = (0, x - 1, x + 2, self.width);
-        } else {
-            x_range = (0, 1, x + 2, self.width);
+        save
+    }
+
+    /// Reads a board written by `Board::save`.
+    pub fn load(save: &str) -> Result<Board, BoardError> {
+        let invalid = |message: String| BoardError::InvalidSave(message);
+        let mut lines = save.lines();
+        let header = lines
+            .next()
+            .ok_or_else(|| invalid("The save is empty".to_owned()))?;
+        let version: u32 = header
+            .strip_prefix(SAVE_HEADER)
+            .and_then(|version| version.trim().parse().ok())
+            .ok_or_else(|| invalid(format!("Not a saved board: {}", header)))?;
+        if !(1..=SAVE_VERSION).contains(&version) {
+            return Err(invalid(format!(
+                "Saved board has version {}, only versions 1 to {} can be loaded",
+                version, SAVE_VERSION
+            )));
         }
-        let y_range: (usize, usize, usize, usize);
-        if y > 0 {
-            y_range = (0, y - 1, y + 2, self.height);
-        } else {
-            y_range = (0, 1, y + 2, self.height);
-        }
-        while removed_mines > 0 {
-            let mut rng = rand::thread_rng();
-            let mine_x: usize;
-            let mine_y: usize;
-            if x > 0 && x + 2 < self.width {
-                if rng.gen_bool(0.5) {
-                    // use the left side range
-                    mine_x = rng.gen_range(x_range.0, x_range.1);
-                } else {
-                    // use the right side range
-                    mine_x = rng.gen_range(x_range.2, x_range.3);
-                }
-            } else if x > 0 {
-                // we can only use the left range
-                mine_x = rng.gen_range(x_range.0, x_range.1);
-            } else if x + 2 < self.width {
-                // we can only use the right range
-                mine_x = rng.gen_range(x_range.2, x_range.3);
+        let settings: Vec<&str> = lines
+            .next()
+            .ok_or_else(|| invalid("The saved board has no settings".to_owned()))?
+            .split(',')
+            .map(|setting| setting.trim())
+            .collect();
+        // Versions 2, 3 and 7 each added a setting to the end of the line
+        let expected = match version {
+            1 => 6,
+            2 => 7,
+            3..=6 => 8,
+            _ => 9,
+        };
+        if settings.len() != expected {
+            return Err(invalid(format!(
+                "Expected {} settings, found {}",
+                expected,
+                settings.len()
+            )));
+        }
+        let parse_error =
+            |setting: &str| invalid(format!("Invalid setting in saved board: {}", setting));
+        let width: usize = settings[0].parse().map_err(|_| parse_error(settings[0]))?;
+        let height: usize = settings[1].parse().map_err(|_| parse_error(settings[1]))?;
+        let mines: usize = settings[2].parse().map_err(|_| parse_error(settings[2]))?;
+        let seed: u64 = settings[3].parse().map_err(|_| parse_error(settings[3]))?;
+        let any_revealed: bool = settings[4].parse().map_err(|_| parse_error(settings[4]))?;
+        // Before version 6 the only choice was whether the board had to be solvable without
+        // guessing
+        let first_click = if version < 6 {
+            let no_guess: bool = settings[5].parse().map_err(|_| parse_error(settings[5]))?;
+            if no_guess {
+                FirstClickPolicy::NoGuess
             } else {
-                //uh there's nowhere to put the mines abort!
-                break;
+                FirstClickPolicy::Opening
             }
-            if y > 0 && y + 2 < self.height {
-                if rng.gen_bool(0.5) {
-                    // use the top side range
-                    mine_y = rng.gen_range(y_range.0, y_range.1);
-                } else {
-                    // use the bottom side range
-                    mine_y = rng.gen_range(y_range.2, y_range.3);
-                }
-            } else if y > 0 {
-                // we can only use the top range
-                mine_y = rng.gen_range(y_range.0, y_range.1);
-            } else if y + 2 < self.height {
-                // we can only use the bottom range
-                mine_y = rng.gen_range(y_range.2, y_range.3);
+        } else {
+            FirstClickPolicy::from_name(settings[5]).ok_or_else(|| parse_error(settings[5]))?
+        };
+        let topology = match settings.get(6) {
+            Some(name) => topology_from_name(name).ok_or_else(|| parse_error(name))?,
+            None => Rc::new(Square),
+        };
+        let wrap: bool = match settings.get(7) {
+            Some(wrap) => wrap.parse().map_err(|_| parse_error(wrap))?,
+            None => false,
+        };
+        let placement = match settings.get(8) {
+            Some(name) => placement_from_name(name).ok_or_else(|| parse_error(name))?,
+            None => Rc::new(Uniform),
+        };
+
+        if width == 0 || height == 0 {
+            return Err(invalid(format!(
+                "A {}x{} board has no tiles",
+                width, height
+            )));
+        }
+        let mut tiles = Vec::new();
+        for y in 0..height {
+            let line = lines
+                .next()
+                .ok_or_else(|| invalid(format!("Missing row {} of tiles", y)))?;
+            // Before version 4 every tile was a single character with nothing between them
+            let row: Vec<String> = if version < 4 {
+                line.chars().map(|c| c.to_string()).collect()
             } else {
-                //uh there's nowhere to put the mines abort!
-                break;
+                line.split(' ').map(|tile| tile.to_owned()).collect()
+            };
+            if row.len() != width {
+                return Err(invalid(format!("Row {} of tiles has the wrong width", y)));
             }
-            if self.tiles[mine_x][mine_y] != Tile::Mine {
-                self.tiles[mine_x][mine_y] = Tile::Mine;
-                removed_mines -= 1;
+            for tile in row {
+                tiles.push(match tile.as_str() {
+                    "*" => Tile::Mine,
+                    "-" => Tile::Void,
+                    count => {
+                        Tile::Safe(Count(count.parse().map_err(|_| {
+                            invalid(format!("Invalid tile in saved board: {}", tile))
+                        })?))
+                    }
+                });
             }
         }
 
-        // Reinitialize the digits of the entire board because a bunch of them are probably wrong now.
-        Self::update_digits(&mut self.tiles, self.height, self.width);
-    }
-
-    /// Reveals all adjacent tiles. returns true if a mine was hit or false if not
-    pub fn reveal_adjacent(&mut self, x: usize, y: usize) -> Result<bool, &'static str> {
-        if !(self.display[x][y] == TileDisplay::Revealed) {
-            return Err("Shouldn't try to reveal adjacent to unrevealed tile");
+        let mut builder = BoardBuilder::new(width, height)
+            .first_click(first_click)
+            .topology(topology.clone())
+            .wrap(wrap)
+            .placement(placement);
+        if tiles.contains(&Tile::Void) {
+            let cells = tiles.iter().map(|&tile| tile != Tile::Void).collect();
+            builder = builder.shape(Shape::from_cells(width, height, cells));
         }
-        if x > 0 {
-            // Check Left
-            if self.display[x - 1][y] == TileDisplay::Hidden {
-                if let Ok(tile) = self.reveal_at(x - 1, y) {
-                    if tile == Tile::Mine {
-                        return Ok(true);
-                    }
-                }
-            }
-            if y < self.height - 1 {
-                // Check Down-Left
-                if self.display[x - 1][y + 1] == TileDisplay::Hidden {
-                    if let Ok(tile) = self.reveal_at(x - 1, y + 1) {
-                        if tile == Tile::Mine {
-                            return Ok(true);
-                        }
-                    }
-                }
-            }
-            if y > 0 {
-                // Check Up-Left
-                if self.display[x - 1][y - 1] == TileDisplay::Hidden {
-                    if let Ok(tile) = self.reveal_at(x - 1, y - 1) {
-                        if tile == Tile::Mine {
-                            return Ok(true);
-                        }
-                    }
-                }
-            }
+        // Until the first reveal there has to be room to move mines out of the way
+        let max_mines = if any_revealed {
+            builder.tiles()
+        } else {
+            builder.max_mines()
+        };
+        if mines > max_mines {
+            return Err(invalid(format!(
+                "A {}x{} board can't have {} mines",
+                width, height, mines
+            )));
         }
-        if x < self.width - 1 {
-            // Check Right
-            if self.display[x + 1][y] == TileDisplay::Hidden {
-                if let Ok(tile) = self.reveal_at(x + 1, y) {
-                    if tile == Tile::Mine {
-                        return Ok(true);
-                    }
-                }
+        if !any_revealed {
+            builder
+                .check_pairs(mines)
+                .map_err(|e| invalid(e.to_string()))?;
+        }
+        builder.check_kind().map_err(|e| invalid(e.to_string()))?;
+        if !height.is_multiple_of(topology.layers()) {
+            return Err(invalid(format!(
+                "A board {} rows high can't be split into {} layers",
+                height,
+                topology.layers()
+            )));
+        }
+        if wrap && !topology.can_wrap(width, height) {
+            return Err(invalid(format!(
+                "A {}x{} {} board can't wrap around",
+                width,
+                height,
+                topology.name()
+            )));
+        }
+        // Recreating the board from its seed also puts the rng back where it was before the
+        // first reveal
+        let mut board = Board::generate(&builder, mines, seed, StdRng::seed_from_u64(seed));
+        board.any_revealed = any_revealed;
+        board.tiles = tiles;
+        for y in 0..height {
+            let row: Vec<char> = lines
+                .next()
+                .ok_or_else(|| invalid(format!("Missing row {} of displays", y)))?
+                .chars()
+                .collect();
+            if row.len() != width {
+                return Err(invalid(format!(
+                    "Row {} of displays has the wrong width",
+                    y
+                )));
             }
-            if y < self.height - 1 {
-                // Check Down-Right
-                if self.display[x + 1][y + 1] == TileDisplay::Hidden {
-                    if let Ok(tile) = self.reveal_at(x + 1, y + 1) {
-                        if tile == Tile::Mine {
-                            return Ok(true);
-                        }
-                    }
+            for (x, c) in row.into_iter().enumerate() {
+                let i = board.index(x, y);
+                board.display[i] = match c {
+                    'H' => TileDisplay::Hidden,
+                    'R' => TileDisplay::Revealed,
+                    'F' => TileDisplay::Flag,
+                    '?' => TileDisplay::Question,
+                    _ => return Err(invalid(format!("Invalid display in saved board: {}", c))),
+                };
+                if board.tiles[i] == Tile::Void && board.display[i] != TileDisplay::Hidden {
+                    return Err(invalid(format!(
+                        "({}, {}) is not part of the board but isn't hidden",
+                        x, y
+                    )));
                 }
             }
-            if y > 0 {
-                // Check Up-Right
-                if self.display[x + 1][y - 1] == TileDisplay::Hidden {
-                    if let Ok(tile) = self.reveal_at(x + 1, y - 1) {
-                        if tile == Tile::Mine {
-                            return Ok(true);
-                        }
+        }
+        let saved_mines = board
+            .tiles
+            .iter()
+            .filter(|tile| **tile == Tile::Mine)
+            .count();
+        if saved_mines != mines {
+            return Err(invalid(format!(
+                "Saved board should have {} mines but has {}",
+                mines, saved_mines
+            )));
+        }
+        // The solver trusts every number it can see, so one that doesn't match its mines would
+        // send it wrong
+        for y in 0..height {
+            for x in 0..width {
+                if let Tile::Safe(Count(count)) = board.tiles[board.index(x, y)] {
+                    let around = board
+                        .neighbours(x, y)
+                        .into_iter()
+                        .filter(|&(nx, ny)| board.tiles[board.index(nx, ny)] == Tile::Mine)
+                        .count();
+                    if count != around {
+                        return Err(invalid(format!(
+                            "({}, {}) is saved as a {} but has {} mines around it",
+                            x, y, count, around
+                        )));
                     }
                 }
             }
         }
-        if y < self.height - 1 {
-            // Check Up
-            if self.display[x][y + 1] == TileDisplay::Hidden {
-                if let Ok(tile) = self.reveal_at(x, y + 1) {
-                    if tile == Tile::Mine {
-                        return Ok(true);
-                    }
-                }
+        board.count_tiles();
+        Ok(board)
+    }
+
+    /// Clears the board and places all of its mines on tiles in its shape, as its placement
+    /// chooses.
+    fn lay_mines(&mut self) {
+        for tile in self.tiles.iter_mut() {
+            if *tile != Tile::Void {
+                *tile = Tile::Safe(Count::ZERO);
             }
         }
-        if y > 0 {
-            // Check Down
-            if self.display[x][y - 1] == TileDisplay::Hidden {
-                if let Ok(tile) = self.reveal_at(x, y - 1) {
-                    if tile == Tile::Mine {
-                        return Ok(true);
-                    }
-                }
+        let max_weight = self.placement.max_weight(self);
+        let (singles, pairs) = self.count_free_groups(|_, _| true);
+        let mut free = (singles.saturating_sub(self.kept_singles), pairs);
+        let mut num_mines = 0;
+        while num_mines < self.mines {
+            let x = self.rng.gen_range(0, self.width);
+            let y = self.rng.gen_range(0, self.height);
+            if self.tiles[self.index(x, y)] == Tile::Mine {
+                continue;
+            }
+            // Partners always get their mines together, so the partner of a free tile is free
+            let group = match self.mine_group(x, y) {
+                Some(group) => group,
+                None => continue,
+            };
+            let left = match take_group(free, group.len(), self.mines - num_mines) {
+                Some(left) if self.keeps(x, y, max_weight) => left,
+                _ => continue,
+            };
+            for &(gx, gy) in group.iter() {
+                self.place_mine(gx, gy);
             }
+            num_mines += group.len();
+            free = left;
         }
-        Ok(false)
+        self.count_tiles();
     }
 
-    pub fn toggle_display_at(&mut self, x: usize, y: usize) -> Result<TileDisplay, String> {
-        if x >= self.width || y >= self.height {
-            return Err(format!(
-                "Index out of bounds: x is {}, y is {}, width is {}, height is {}",
-                x, y, self.width, self.height
-            ));
+    /// Returns (x, y) and its partner under the placement, if it has one, as every tile that gets
+    /// a mine when (x, y) does. Returns None if either is outside the board's shape, as then
+    /// neither can have a mine.
+    fn mine_group(&self, x: usize, y: usize) -> Option<Vec<(usize, usize)>> {
+        let mut group = vec![(x, y)];
+        group.extend(self.placement.partner(x, y, self.width, self.height));
+        if group
+            .iter()
+            .any(|&(gx, gy)| self.tiles[self.index(gx, gy)] == Tile::Void)
+        {
+            None
+        } else {
+            Some(group)
         }
-        let next = match self.display[x][y] {
-            TileDisplay::Hidden => TileDisplay::Flag,
-            TileDisplay::Flag => TileDisplay::Question,
-            TileDisplay::Question => TileDisplay::Hidden,
-            TileDisplay::Revealed => TileDisplay::Revealed,
-        };
-        self.display[x][y] = next;
-        Ok(self.display[x][y])
     }
 
-    pub fn get_display_at(&mut self, x: usize, y: usize) -> Result<TileDisplay, String> {
-        if x >= self.width {
-            return Err(format!("x must be less than {}; it was {}", self.width, x));
+    /// Counts the safe tiles `free` allows that would take a mine on their own, and the pairs of
+    /// them that would take mines together.
+    fn count_free_groups(&self, free: impl Fn(usize, usize) -> bool) -> (usize, usize) {
+        count_groups(&*self.placement, self.width, self.height, |x, y| {
+            matches!(self.tiles[self.index(x, y)], Tile::Safe(_)) && free(x, y)
+        })
+    }
+
+    /// Decides whether a tile picked at random gets a mine, with a chance of its weight under the
+    /// placement over `max_weight`.
+    fn keeps(&mut self, x: usize, y: usize, max_weight: f64) -> bool {
+        let weight = self.placement.weight(self, x, y);
+        // Not drawing for tiles that are always kept leaves uniform layouts as they always were
+        weight >= max_weight || self.rng.gen_bool(weight / max_weight)
+    }
+
+    /// Returns where the tile at (x, y) is stored.
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    /// Puts a mine on a safe tile and adds it to the numbers around it.
+    fn place_mine(&mut self, x: usize, y: usize) {
+        let i = self.index(x, y);
+        self.tiles[i] = Tile::Mine;
+        for (nx, ny) in self.neighbours(x, y) {
+            let n = self.index(nx, ny);
+            if let Tile::Safe(Count(count)) = self.tiles[n] {
+                self.tiles[n] = Tile::Safe(Count(count + 1));
+            }
         }
-        if y >= self.height {
-            return Err(format!("y must be less than {}; it was {}", self.height, y));
+    }
+
+    /// Takes the mine off a tile, numbering the tile and taking it off the numbers around it.
+    fn remove_mine(&mut self, x: usize, y: usize) {
+        let mut count = 0usize;
+        for (nx, ny) in self.neighbours(x, y) {
+            let n = self.index(nx, ny);
+            match self.tiles[n] {
+                Tile::Mine => count += 1,
+                Tile::Safe(Count(count)) => self.tiles[n] = Tile::Safe(Count(count - 1)),
+                Tile::Void => (),
+            }
         }
-        Ok(self.display[x][y])
+        let i = self.index(x, y);
+        self.tiles[i] = Tile::Safe(Count(count));
     }
 
-    pub fn get_tile_at(&mut self, x: usize, y: usize) -> Result<Tile, String> {
-        if x >= self.width {
-            return Err(format!("x must be less than {}; it was {}", self.width, x));
+    /// Counts the safe tiles that aren't revealed and the mines that are. Needed whenever the
+    /// layout changes.
+    fn count_tiles(&mut self) {
+        self.hidden_safe = 0;
+        self.revealed_mines = 0;
+        for (tile, display) in self.tiles.iter().zip(self.display.iter()) {
+            match (tile, display) {
+                (Tile::Mine, TileDisplay::Revealed) => self.revealed_mines += 1,
+                (Tile::Safe(_), TileDisplay::Revealed) | (Tile::Mine, _) => (),
+                (Tile::Safe(_), _) => self.hidden_safe += 1,
+                (Tile::Void, _) => (),
+            }
         }
-        if y >= self.height {
-            return Err(format!("y must be less than {}; it was {}", self.height, y));
+    }
+
+    /// Should be called the first time a tile is revealed on a no guess board.
+    /// Lays out new mines until the board can be solved by the solver starting from (x, y), and
+    /// fails if none of `NO_GUESS_ATTEMPTS` layouts can be.
+    fn guarantee_no_guess(&mut self, x: usize, y: usize) -> Result<(), BoardError> {
+        for _ in 0..NO_GUESS_ATTEMPTS {
+            if self.solvable_from(x, y) {
+                return Ok(());
+            }
+            self.lay_mines();
+            self.guarantee_zero(x, y)?;
         }
-        Ok(self.tiles[x][y])
+        Err(BoardError::NoGuessLayout {
+            mines: self.mines,
+            attempts: NO_GUESS_ATTEMPTS,
+        })
     }
 
-    pub fn check_victory(&mut self) -> bool {
-        for x in 0..self.width {
-            for y in 0..self.height {
-                match self.tiles[x][y] {
-                    Tile::Safe(_) => {
-                        if self.display[x][y] != TileDisplay::Revealed {
-                            return false;
-                        }
-                    }
-                    _ => (),
+    /// Plays a copy of the board from (x, y), only ever revealing tiles the solver proves are
+    /// safe, and reports whether that clears the board.
+    fn solvable_from(&self, x: usize, y: usize) -> bool {
+        let mut board = self.clone();
+        board.any_revealed = true;
+        board.reveal_at(x, y).unwrap();
+        loop {
+            let safe: Vec<(usize, usize)> = solver::solve(&board)
+                .into_iter()
+                .filter(|deduction| !deduction.mine)
+                .map(|deduction| (deduction.x, deduction.y))
+                .collect();
+            if safe.is_empty() {
+                break;
+            }
+            for (x, y) in safe {
+                // Earlier reveals may have opened this tile up already, or even won the game
+                if board.check_victory() {
+                    break;
+                }
+                match board.reveal_at(x, y) {
+                    Ok(_) | Err(BoardError::AlreadyRevealed { .. }) => (),
+                    Err(e) => panic!("The solver deduced an impossible tile: {}", e),
                 }
             }
         }
-        true
+        board.check_victory()
     }
-}
 
-impl Display for Board {
+    /// Changes the display of a tile, recording the change in the current move.
+    fn set_display(&mut self, x: usize, y: usize, display: TileDisplay) {
+        let before = self.display[self.index(x, y)];
+        if before != display {
+            self.current.display.push((x, y, before, display));
+            self.put_display(x, y, display);
+        }
+    }
+
+    /// Changes the display of a tile without recording it, keeping count of the hidden safe tiles
+    /// and revealed mines.
+    fn put_display(&mut self, x: usize, y: usize, display: TileDisplay) {
+        let was_revealed = self.display[self.index(x, y)] == TileDisplay::Revealed;
+        let is_revealed = display == TileDisplay::Revealed;
+        if was_revealed != is_revealed {
+            match (self.tiles[self.index(x, y)], is_revealed) {
+                (Tile::Mine, true) => self.revealed_mines += 1,
+                (Tile::Mine, false) => self.revealed_mines -= 1,
+                (Tile::Safe(_), true) => self.hidden_safe -= 1,
+                (Tile::Safe(_), false) => self.hidden_safe += 1,
+                (Tile::Void, _) => (),
+            }
+        }
+        let i = self.index(x, y);
+        self.display[i] = display;
+    }
+
+    /// Works out whether the tiles just revealed won the game.
+    fn finish_reveal(&mut self, outcome: &mut RevealOutcome) {
+        outcome.won = !outcome.hit_mine() && self.check_victory();
+        if outcome.won {
+            self.events.push(BoardEvent::GameWon);
+        }
+    }
+
+    /// Takes every event that has happened since the last call, oldest first.
+    pub fn drain_events(&mut self) -> Vec<BoardEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Records the layout as it was before the current move changed it, unless the move already
+    /// has. The layout after is recorded when the move ends.
+    fn record_layout(&mut self) {
+        if self.current.layout.is_none() {
+            self.current.layout = Some(LayoutChange {
+                tiles_before: self.tiles.clone(),
+                rng_before: self.rng.clone(),
+                tiles_after: self.tiles.clone(),
+                rng_after: self.rng.clone(),
+            });
+        }
+    }
+
+    /// Adds everything changed since the last move to the history as a single move.
+    fn end_move(&mut self) {
+        let mut finished = std::mem::take(&mut self.current);
+        if let Some(layout) = &mut finished.layout {
+            layout.tiles_after = self.tiles.clone();
+            layout.rng_after = self.rng.clone();
+        }
+        if !finished.display.is_empty() || finished.layout.is_some() {
+            self.history.push(finished);
+            self.undone.clear();
+        }
+    }
+
+    /// Takes back the last change made to the board. Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let last = match self.history.pop() {
+            Some(last) => last,
+            None => return false,
+        };
+        for &(x, y, before, _) in last.display.iter().rev() {
+            self.put_display(x, y, before);
+        }
+        if let Some(layout) = &last.layout {
+            self.tiles = layout.tiles_before.clone();
+            self.rng = layout.rng_before.clone();
+            self.count_tiles();
+        }
+        if last.started {
+            self.any_revealed = false;
+        }
+        self.undone.push(last);
+        self.events.push(BoardEvent::MoveUndone);
+        true
+    }
+
+    /// Makes the last undone change again. Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let next = match self.undone.pop() {
+            Some(next) => next,
+            None => return false,
+        };
+        if let Some(layout) = &next.layout {
+            self.tiles = layout.tiles_after.clone();
+            self.rng = layout.rng_after.clone();
+            self.count_tiles();
+        }
+        if next.started {
+            self.any_revealed = true;
+        }
+        for &(x, y, _, after) in next.display.iter() {
+            self.put_display(x, y, after);
+        }
+        self.history.push(next);
+        self.events.push(BoardEvent::MoveRedone);
+        true
+    }
+
+    pub fn reveal_all(&mut self) {
+        for x in 0..self.width {
+            for y in 0..self.height {
+                if self.tiles[self.index(x, y)] != Tile::Void {
+                    self.set_display(x, y, TileDisplay::Revealed);
+                }
+            }
+        }
+        self.end_move();
+    }
+
+    /// Reveals (x, y), along with the whole opening if it is a zero.
+    pub fn reveal_at(&mut self, x: usize, y: usize) -> Result<RevealOutcome, BoardError> {
+        self.check_shape(x, y)?;
+        self.check_playing()?;
+        if self.display[self.index(x, y)] == TileDisplay::Revealed {
+            return Err(BoardError::AlreadyRevealed { x, y });
+        }
+        let mut outcome = RevealOutcome::default();
+        let result = self.reveal(x, y, &mut outcome);
+        self.end_move();
+        result?;
+        self.finish_reveal(&mut outcome);
+        Ok(outcome)
+    }
+
+    fn reveal(
+        &mut self,
+        x: usize,
+        y: usize,
+        outcome: &mut RevealOutcome,
+    ) -> Result<(), BoardError> {
+        self.check_bounds(x, y)?;
+        if !self.any_revealed {
+            let tiles_before = self.tiles.clone();
+            let rng_before = self.rng.clone();
+            let settled = match self.first_click {
+                FirstClickPolicy::Unprotected => Ok(()),
+                FirstClickPolicy::SafeOnly => self.move_mines_off(&[(x, y)]),
+                FirstClickPolicy::Opening | FirstClickPolicy::Kind => self.guarantee_zero(x, y),
+                FirstClickPolicy::NoGuess => self
+                    .guarantee_zero(x, y)
+                    .and_then(|()| self.guarantee_no_guess(x, y)),
+            };
+            // Nothing is revealed if no layout works, but the rng moves on so another try starts
+            // from new layouts
+            if let Err(e) = settled {
+                self.tiles = tiles_before;
+                self.count_tiles();
+                return Err(e);
+            }
+            self.current.started = true;
+            self.current.layout = Some(LayoutChange {
+                tiles_before,
+                rng_before,
+                tiles_after: self.tiles.clone(),
+                rng_after: self.rng.clone(),
+            });
+            self.events.push(BoardEvent::GameStarted);
+        } else if self.first_click == FirstClickPolicy::Kind {
+            self.settle_kindly(x, y);
+        }
+        self.any_revealed = true;
+        let revealed_before = outcome.revealed.len();
+        self.reveal_tile(x, y, outcome);
+        // Spread out from each zero that is uncovered, one tile at a time
+        let mut zeros = VecDeque::new();
+        if self.tiles[self.index(x, y)] == Tile::Safe(Count::ZERO) {
+            zeros.push_back((x, y));
+        }
+        while let Some((zx, zy)) = zeros.pop_front() {
+            for (nx, ny) in self.neighbours(zx, zy) {
+                if self.display[self.index(nx, ny)] != TileDisplay::Hidden {
+                    continue;
+                }
+                self.reveal_tile(nx, ny, outcome);
+                if self.tiles[self.index(nx, ny)] == Tile::Safe(Count::ZERO) {
+                    zeros.push_back((nx, ny));
+                }
+            }
+        }
+        let cells = outcome.revealed.len() - revealed_before;
+        if cells > 1 {
+            self.events
+                .push(BoardEvent::OpeningCascaded { x, y, cells });
+        }
+        Ok(())
+    }
+
+    fn reveal_tile(&mut self, x: usize, y: usize, outcome: &mut RevealOutcome) {
+        if self.display[self.index(x, y)] != TileDisplay::Revealed {
+            outcome.revealed.push((x, y));
+            self.set_display(x, y, TileDisplay::Revealed);
+            self.events.push(BoardEvent::CellRevealed { x, y });
+            if self.tiles[self.index(x, y)] == Tile::Mine {
+                outcome.exploded.push((x, y));
+                self.events.push(BoardEvent::MineExploded { x, y });
+            }
+        }
+    }
+
+    /// Should be called on a kind board just before (x, y) is revealed. Makes it safe if no hidden
+    /// tile could be proven safe, and a mine if one could, as long as some layout that fits what
+    /// has been revealed allows it. When none is found, which includes the search giving up on a
+    /// board with too many possible layouts, the layout is left as it is and (x, y) is revealed
+    /// as whatever it already is.
+    fn settle_kindly(&mut self, x: usize, y: usize) {
+        let (safe_elsewhere, chance) = kind::safe_move(self, x, y);
+        // A tile that is certain either way has nothing to settle
+        if chance <= 0. || chance >= 1. {
+            return;
+        }
+        if (self.tiles[self.index(x, y)] == Tile::Mine) == safe_elsewhere {
+            return;
+        }
+        self.record_layout();
+        let mut rng = self.rng.clone();
+        let mines = kind::relayout(self, x, y, safe_elsewhere, &mut rng);
+        self.rng = rng;
+        if let Some(mines) = mines {
+            self.set_mines(&mines);
+        }
+    }
+
+    /// Replaces the layout with one that has mines on exactly the given tiles.
+    fn set_mines(&mut self, mines: &[(usize, usize)]) {
+        debug_assert_eq!(mines.len(), self.mines);
+        for tile in self.tiles.iter_mut() {
+            if *tile != Tile::Void {
+                *tile = Tile::Safe(Count::ZERO);
+            }
+        }
+        for &(x, y) in mines {
+            self.place_mine(x, y);
+        }
+        self.count_tiles();
+    }
+
+    /// Should be called the first time a tile is revealed. Moves any mines on (x, y) or the tiles
+    /// around it to random tiles elsewhere.
+    fn guarantee_zero(&mut self, x: usize, y: usize) -> Result<(), BoardError> {
+        let mut cleared = self.neighbours(x, y);
+        cleared.push((x, y));
+        self.move_mines_off(&cleared)
+    }
+
+    /// Moves any mines on the `cleared` tiles to random tiles elsewhere. Under a uniform placement
+    /// each mine is equally likely to land on any tile that is still free, however far it is from
+    /// the cleared tiles. Other placements weight the tiles the same way as when laying mines, and
+    /// the partners of the cleared tiles are cleared as well, so mines move in pairs. Fails,
+    /// leaving the cleared mines off the board, if an odd one out has nowhere to go.
+    fn move_mines_off(&mut self, cleared: &[(usize, usize)]) -> Result<(), BoardError> {
+        let mut cleared = cleared.to_vec();
+        for i in 0..cleared.len() {
+            let (cx, cy) = cleared[i];
+            if let Some(partner) = self.placement.partner(cx, cy, self.width, self.height) {
+                if !cleared.contains(&partner) {
+                    cleared.push(partner);
+                }
+            }
+        }
+        let mut removed_mines = 0;
+        for &(cx, cy) in cleared.iter() {
+            if self.tiles[self.index(cx, cy)] == Tile::Mine {
+                self.remove_mine(cx, cy);
+                removed_mines += 1;
+            }
+        }
+        if removed_mines > 0 {
+            let mut free: Vec<(usize, usize)> = (0..self.height)
+                .flat_map(|fy| (0..self.width).map(move |fx| (fx, fy)))
+                .filter(|&(fx, fy)| {
+                    matches!(self.tiles[self.index(fx, fy)], Tile::Safe(_))
+                        && !cleared.contains(&(fx, fy))
+                })
+                .collect();
+            let mut groups = self.count_free_groups(|fx, fy| !cleared.contains(&(fx, fy)));
+            // Boards laid out by the builder always leave room for this, but a saved layout may
+            // not
+            if take_group(groups, 0, removed_mines).is_none() {
+                return Err(BoardError::UnpairedMine {
+                    mines: self.mines,
+                    placement: self.placement.name(),
+                });
+            }
+            let max_weight = self.placement.max_weight(self);
+            let mut placed = 0;
+            while placed < removed_mines {
+                let i = self.rng.gen_range(0, free.len());
+                let (mx, my) = free[i];
+                // A tile whose partner isn't free can't take a mine
+                let group = match self.mine_group(mx, my) {
+                    Some(group) if group.iter().all(|tile| free.contains(tile)) => group,
+                    _ => continue,
+                };
+                let left = match take_group(groups, group.len(), removed_mines - placed) {
+                    Some(left) if self.keeps(mx, my, max_weight) => left,
+                    _ => continue,
+                };
+                for tile in group.iter() {
+                    let j = free.iter().position(|free| free == tile).unwrap();
+                    free.swap_remove(j);
+                    self.place_mine(tile.0, tile.1);
+                }
+                placed += group.len();
+                groups = left;
+            }
+        }
+        self.count_tiles();
+        Ok(())
+    }
+
+    /// Chords on a revealed number: if it has exactly as many flags around it as its number,
+    /// every tile around it that isn't flagged is revealed, mines included.
+    pub fn chord(&mut self, x: usize, y: usize) -> Result<RevealOutcome, BoardError> {
+        self.check_shape(x, y)?;
+        self.check_playing()?;
+        if self.display[self.index(x, y)] != TileDisplay::Revealed {
+            return Err(BoardError::NotRevealed { x, y });
+        }
+        let neighbours = self.neighbours(x, y);
+        if let Tile::Safe(Count(number)) = self.tiles[self.index(x, y)] {
+            let flags = neighbours
+                .iter()
+                .filter(|&&(nx, ny)| self.display[self.index(nx, ny)] == TileDisplay::Flag)
+                .count();
+            if flags != number {
+                return Err(BoardError::WrongFlagCount {
+                    x,
+                    y,
+                    flags,
+                    number,
+                });
+            }
+        }
+        let mut outcome = RevealOutcome::default();
+        // Opening up one neighbour may already have revealed the next, so each is checked as it
+        // comes
+        for (nx, ny) in neighbours {
+            match self.display[self.index(nx, ny)] {
+                TileDisplay::Hidden | TileDisplay::Question => self.reveal(nx, ny, &mut outcome)?,
+                TileDisplay::Flag | TileDisplay::Revealed => (),
+            }
+        }
+        self.end_move();
+        self.finish_reveal(&mut outcome);
+        Ok(outcome)
+    }
+
+    /// Cycles a tile between hidden, flagged and questioned.
+    pub fn toggle_display_at(&mut self, x: usize, y: usize) -> Result<TileDisplay, BoardError> {
+        self.check_shape(x, y)?;
+        self.check_playing()?;
+        let next = match self.display[self.index(x, y)] {
+            TileDisplay::Hidden => TileDisplay::Flag,
+            TileDisplay::Flag => TileDisplay::Question,
+            TileDisplay::Question => TileDisplay::Hidden,
+            TileDisplay::Revealed => return Err(BoardError::AlreadyRevealed { x, y }),
+        };
+        self.set_display(x, y, next);
+        self.end_move();
+        match next {
+            TileDisplay::Flag => self.events.push(BoardEvent::FlagPlaced { x, y }),
+            TileDisplay::Question => self.events.push(BoardEvent::FlagRemoved { x, y }),
+            _ => (),
+        }
+        Ok(next)
+    }
+
+    pub fn get_display_at(&self, x: usize, y: usize) -> Result<TileDisplay, BoardError> {
+        self.check_bounds(x, y)?;
+        Ok(self.display[self.index(x, y)])
+    }
+
+    pub fn get_tile_at(&self, x: usize, y: usize) -> Result<Tile, BoardError> {
+        self.check_bounds(x, y)?;
+        Ok(self.tiles[self.index(x, y)])
+    }
+
+    fn check_bounds(&self, x: usize, y: usize) -> Result<(), BoardError> {
+        if x >= self.width || y >= self.height {
+            return Err(BoardError::OutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            });
+        }
+        Ok(())
+    }
+
+    /// Fails if (x, y) is off the board or outside its shape.
+    fn check_shape(&self, x: usize, y: usize) -> Result<(), BoardError> {
+        self.check_bounds(x, y)?;
+        if self.tiles[self.index(x, y)] == Tile::Void {
+            return Err(BoardError::OutsideShape { x, y });
+        }
+        Ok(())
+    }
+
+    /// Fails if the game has already been won or lost.
+    fn check_playing(&self) -> Result<(), BoardError> {
+        if self.mine_revealed() || self.check_victory() {
+            return Err(BoardError::GameOver);
+        }
+        Ok(())
+    }
+
+    /// Returns the coordinates of every tile adjacent to (x, y), leaving out tiles outside the
+    /// board's shape.
+    pub fn neighbours(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let mut neighbours = self
+            .topology
+            .neighbours(x, y, self.width, self.height, self.wrap);
+        neighbours.retain(|&(nx, ny)| self.tiles[self.index(nx, ny)] != Tile::Void);
+        neighbours
+    }
+
+    /// Returns true if a mine has been revealed, meaning the game was lost.
+    pub fn mine_revealed(&self) -> bool {
+        self.revealed_mines > 0
+    }
+
+    /// Returns the number of tiles currently marked with a flag.
+    pub fn count_flags(&self) -> usize {
+        self.display
+            .iter()
+            .filter(|display| **display == TileDisplay::Flag)
+            .count()
+    }
+
+    /// Returns the zeros of each opening: a connected area of zeros that is cleared by a single
+    /// click, along with the numbers around it.
+    fn opening_zeros(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut seen = vec![false; self.width * self.height];
+        let mut openings = Vec::new();
+        for x in 0..self.width {
+            for y in 0..self.height {
+                if seen[self.index(x, y)] || self.tiles[self.index(x, y)] != Tile::Safe(Count::ZERO)
+                {
+                    continue;
+                }
+                seen[self.index(x, y)] = true;
+                let mut stack = vec![(x, y)];
+                let mut zeros = Vec::new();
+                while let Some((zx, zy)) = stack.pop() {
+                    zeros.push((zx, zy));
+                    for (nx, ny) in self.neighbours(zx, zy) {
+                        let n = self.index(nx, ny);
+                        if !seen[n] && self.tiles[n] == Tile::Safe(Count::ZERO) {
+                            seen[n] = true;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+                openings.push(zeros);
+            }
+        }
+        openings
+    }
+
+    /// Returns the numbers that don't border a zero, so each one needs its own click.
+    fn isolated_numbers(&self) -> Vec<(usize, usize)> {
+        let mut numbers = Vec::new();
+        for x in 0..self.width {
+            for y in 0..self.height {
+                if let Tile::Safe(count) = self.tiles[self.index(x, y)] {
+                    let by_zero = self
+                        .neighbours(x, y)
+                        .into_iter()
+                        .any(|(nx, ny)| self.tiles[self.index(nx, ny)] == Tile::Safe(Count::ZERO));
+                    if count != Count::ZERO && !by_zero {
+                        numbers.push((x, y));
+                    }
+                }
+            }
+        }
+        numbers
+    }
+
+    /// Returns the number of openings on the board.
+    pub fn openings(&self) -> usize {
+        self.opening_zeros().len()
+    }
+
+    /// Returns the number of islands: connected areas of numbers that don't border any opening.
+    pub fn islands(&self) -> usize {
+        let isolated = self.isolated_numbers();
+        let mut remaining = vec![false; self.width * self.height];
+        for &(x, y) in isolated.iter() {
+            remaining[self.index(x, y)] = true;
+        }
+        let mut islands = 0;
+        for (x, y) in isolated {
+            if !remaining[self.index(x, y)] {
+                continue;
+            }
+            islands += 1;
+            remaining[self.index(x, y)] = false;
+            let mut stack = vec![(x, y)];
+            while let Some((ix, iy)) = stack.pop() {
+                for (nx, ny) in self.neighbours(ix, iy) {
+                    let n = self.index(nx, ny);
+                    if remaining[n] {
+                        remaining[n] = false;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+        }
+        islands
+    }
+
+    /// Returns the board's 3BV (Bechtel's Board Benchmark Value): the fewest clicks needed to
+    /// clear it without flagging. The mines only settle on the first reveal, so this is only
+    /// meaningful once the game has started.
+    pub fn three_bv(&self) -> usize {
+        self.openings() + self.isolated_numbers().len()
+    }
+
+    /// Returns how much of the board's 3BV has been revealed.
+    pub fn solved_three_bv(&self) -> usize {
+        let revealed =
+            |&(x, y): &(usize, usize)| self.display[self.index(x, y)] == TileDisplay::Revealed;
+        let openings = self
+            .opening_zeros()
+            .into_iter()
+            .filter(|zeros| zeros.iter().any(revealed))
+            .count();
+        openings
+            + self
+                .isolated_numbers()
+                .iter()
+                .filter(|cell| revealed(cell))
+                .count()
+    }
+
+    /// Returns true if every safe tile has been revealed.
+    pub fn check_victory(&self) -> bool {
+        self.hidden_safe == 0
+    }
+}
+
+/// Counts the tiles `free` allows that get a mine on their own under the placement, and the pairs
+/// of them that get mines together. A tile whose partner isn't free is left out.
+fn count_groups(
+    placement: &dyn MinePlacement,
+    width: usize,
+    height: usize,
+    free: impl Fn(usize, usize) -> bool,
+) -> (usize, usize) {
+    let mut singles = 0;
+    let mut paired = 0;
+    for y in 0..height {
+        for x in 0..width {
+            if !free(x, y) {
+                continue;
+            }
+            match placement.partner(x, y, width, height) {
+                None => singles += 1,
+                Some((px, py)) if free(px, py) => paired += 1,
+                Some(_) => (),
+            }
+        }
+    }
+    (singles, paired / 2)
+}
+
+/// Takes a group of `size` tiles, 1 for a single or 2 for a pair, from the free `(singles, pairs)`
+/// while `mines` are still to be laid. Returns what is left free, or None if the group doesn't fit
+/// or would leave the rest of the mines without room, which for an odd number of them means
+/// keeping a single free. A size of 0 only checks that the mines fit.
+fn take_group(
+    (singles, pairs): (usize, usize),
+    size: usize,
+    mines: usize,
+) -> Option<(usize, usize)> {
+    let (singles, pairs) = match size {
+        0 => (singles, pairs),
+        1 => (singles.checked_sub(1)?, pairs),
+        _ => (singles, pairs.checked_sub(1)?),
+    };
+    let mines = mines.checked_sub(size)?;
+    if mines <= singles + 2 * pairs && (mines.is_multiple_of(2) || singles > 0) {
+        Some((singles, pairs))
+    } else {
+        None
+    }
+}
+
+impl Display for Board {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for z in 0..self.depth() {
+            if self.depth() > 1 {
+                writeln!(f)?;
+                write!(f, "Layer {}:", z)?;
+            }
+            write!(f, "{}", self.layer(z))?;
+        }
+        Ok(())
+    }
+}
+
+/// One layer of a board, which prints with its rows numbered from the top of the layer.
+#[derive(Debug, Copy, Clone)]
+pub struct Layer<'a> {
+    board: &'a Board,
+    z: usize,
+}
+
+impl Display for Layer<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "")?;
+        let board = self.board;
+        // Every tile is made wide enough for the largest count the board could have
+        let inner = board.most_neighbours().to_string().len();
+        writeln!(f)?;
         write!(f, "   ")?;
-        if self.width < 11 {
-            for i in 0..self.width {
-                write!(f, " {} ", i)?;
+        for i in 0..board.width {
+            write!(f, " {:<1$}", i, inner + 1)?;
+        }
+        writeln!(f)?;
+        let rows = board.layer_height();
+        for row in 0..rows {
+            let y = self.z * rows + row;
+            write!(f, "{:2} ", row)?;
+            write!(f, "{:1$}", "", board.topology.row_indent(y, inner + 2))?;
+            for x in 0..board.width {
+                if board.tiles[board.index(x, y)] == Tile::Void {
+                    write!(f, "{:1$}", "", inner + 2)?;
+                    continue;
+                }
+                let (left, right) = board.topology.tile_edges(x, y);
+                let tile = match board.display[board.index(x, y)] {
+                    TileDisplay::Revealed => board.tiles[board.index(x, y)].to_string(),
+                    TileDisplay::Hidden => " ".to_owned(),
+                    TileDisplay::Flag => "!".to_owned(),
+                    TileDisplay::Question => "?".to_owned(),
+                };
+                write!(f, "{}{:>3$}{}", left, tile, right, inner)?;
             }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The clicks of each kind made during a game.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct Clicks {
+    pub left: usize,
+    pub right: usize,
+    pub chord: usize,
+}
+
+impl Clicks {
+    pub fn total(&self) -> usize {
+        self.left + self.right + self.chord
+    }
+}
+
+/// How well a finished game was played.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GameStats {
+    pub three_bv: usize,
+    pub solved_three_bv: usize,
+    pub openings: usize,
+    pub islands: usize,
+    pub clicks: Clicks,
+    /// Seconds the game took
+    pub time: f64,
+    /// How much help the first click had, as games under different policies aren't comparable
+    pub first_click: FirstClickPolicy,
+}
+
+impl GameStats {
+    /// Measures a game on the board as it was when the game ended. For a lost game this has to be
+    /// done before the rest of the board is revealed.
+    pub fn new(board: &Board, clicks: Clicks, time: f64) -> GameStats {
+        GameStats {
+            three_bv: board.three_bv(),
+            solved_three_bv: board.solved_three_bv(),
+            openings: board.openings(),
+            islands: board.islands(),
+            clicks,
+            time,
+            first_click: board.first_click(),
+        }
+    }
+
+    /// The 3BV cleared per second.
+    pub fn three_bv_per_second(&self) -> f64 {
+        if self.time > 0. {
+            self.solved_three_bv as f64 / self.time
+        } else {
+            0.
+        }
+    }
+
+    /// Index of efficiency: the board's 3BV divided by the clicks made.
+    pub fn ioe(&self) -> f64 {
+        if self.clicks.total() > 0 {
+            self.three_bv as f64 / self.clicks.total() as f64
+        } else {
+            0.
+        }
+    }
+
+    /// The 3BV cleared per click as a percentage, so a lost game is measured by what was cleared.
+    pub fn efficiency(&self) -> f64 {
+        if self.clicks.total() > 0 {
+            self.solved_three_bv as f64 * 100. / self.clicks.total() as f64
+        } else {
+            0.
+        }
+    }
+}
+
+impl Display for GameStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "3BV {}/{}  3BV/s {:.2}",
+            self.solved_three_bv,
+            self.three_bv,
+            self.three_bv_per_second()
+        )?;
+        writeln!(f, "Openings {}  Islands {}", self.openings, self.islands)?;
+        writeln!(
+            f,
+            "Clicks {}: {}L {}R {}C",
+            self.clicks.total(),
+            self.clicks.left,
+            self.clicks.right,
+            self.clicks.chord
+        )?;
+        writeln!(f, "IOE {:.2}  Eff {:.0}%", self.ioe(), self.efficiency())?;
+        write!(f, "First click: {}", self.first_click)
+    }
+}
+
+/// Where a game is up to.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Phase {
+    NotStarted,
+    Playing,
+    Won,
+    Lost,
+}
+
+/// A game on a board, with the rules both frontends share: chording, flag counting, win and loss
+/// detection, and keeping time and count of the clicks made.
+#[derive(Debug, Clone)]
+pub struct Game {
+    board: Board,
+    phase: Phase,
+    flags: usize,
+    time: f64,
+    clicks: Clicks,
+}
+
+impl Game {
+    pub fn new(board: Board) -> Game {
+        Game::resume(board, 0.)
+    }
+
+    /// Carries on a game that had already been played for `time` seconds on the board.
+    pub fn resume(board: Board, time: f64) -> Game {
+        let mut game = Game {
+            flags: board.count_flags(),
+            board,
+            phase: Phase::NotStarted,
+            time,
+            clicks: Clicks::default(),
+        };
+        game.update_phase();
+        game
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Seconds played since the first reveal.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Moves the clock on, if the game is being played.
+    pub fn tick(&mut self, seconds: f64) {
+        if self.phase == Phase::Playing {
+            self.time += seconds;
+        }
+    }
+
+    /// The number of mines less the number of flags, which is negative when there are too many
+    /// flags.
+    pub fn mines_left(&self) -> i64 {
+        self.board.mines as i64 - self.flags as i64
+    }
+
+    /// Takes every event that has happened on the board since the last call, oldest first.
+    pub fn drain_events(&mut self) -> Vec<BoardEvent> {
+        self.board.drain_events()
+    }
+
+    /// Measures the game so far. For a lost game this has to be done before the rest of the board
+    /// is revealed.
+    pub fn stats(&self) -> GameStats {
+        GameStats::new(&self.board, self.clicks, self.time)
+    }
+
+    pub fn reveal(&mut self, x: usize, y: usize) -> Result<RevealOutcome, BoardError> {
+        let outcome = self.board.reveal_at(x, y)?;
+        self.clicks.left += 1;
+        self.board_changed();
+        Ok(outcome)
+    }
+
+    /// Cycles a tile between hidden, flagged and questioned.
+    pub fn toggle(&mut self, x: usize, y: usize) -> Result<TileDisplay, BoardError> {
+        let display = self.board.toggle_display_at(x, y)?;
+        self.clicks.right += 1;
+        match display {
+            TileDisplay::Flag => self.flags += 1,
+            TileDisplay::Question => self.flags -= 1,
+            _ => (),
+        }
+        Ok(display)
+    }
+
+    /// Reveals the tiles around a number that already has as many flags around it as its number.
+    pub fn chord(&mut self, x: usize, y: usize) -> Result<RevealOutcome, BoardError> {
+        let outcome = self.board.chord(x, y)?;
+        self.clicks.chord += 1;
+        self.board_changed();
+        Ok(outcome)
+    }
+
+    /// Reveals every tile, as is done once a game is lost or given up.
+    pub fn reveal_all(&mut self) {
+        self.board.reveal_all();
+        self.board_changed();
+    }
+
+    /// Takes back the last move, along with the move that lost the game if it was lost. Returns
+    /// the number of moves made on the board that were taken back.
+    pub fn undo(&mut self) -> usize {
+        let mut undone = 0;
+        while (undone == 0 || self.board.mine_revealed()) && self.board.undo() {
+            undone += 1;
+        }
+        self.board_changed();
+        undone
+    }
+
+    /// Makes the last undone move again. Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let redone = self.board.redo();
+        self.board_changed();
+        redone
+    }
+
+    /// Catches up with a change that may have revealed flags as well as won or lost the game.
+    fn board_changed(&mut self) {
+        self.flags = self.board.count_flags();
+        self.update_phase();
+    }
+
+    fn update_phase(&mut self) {
+        self.phase = if self.board.mine_revealed() {
+            Phase::Lost
+        } else if self.board.check_victory() {
+            Phase::Won
+        } else if self.board.is_started() {
+            Phase::Playing
         } else {
-            for i in 0..10 {
-                write!(f, " {} ", i)?;
+            Phase::NotStarted
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Boards generated for each test. Enough that every tile expects hundreds of mines.
+    const SAMPLES: u64 = 4000;
+
+    /// Counts how often each tile of `SAMPLES` seeded boards is a mine, after `first_reveal` if
+    /// there is one.
+    fn mine_counts(builder: BoardBuilder, first_reveal: Option<(usize, usize)>) -> Vec<usize> {
+        let mut counts = Vec::new();
+        for seed in 0..SAMPLES {
+            let mut board = builder.clone().seed(seed).build().unwrap();
+            if let Some((x, y)) = first_reveal {
+                board.reveal_at(x, y).unwrap();
             }
-            for i in 10..self.width {
-                write!(f, " {}", i)?;
+            counts.resize(board.tiles.len(), 0);
+            for (count, tile) in counts.iter_mut().zip(board.tiles.iter()) {
+                if *tile == Tile::Mine {
+                    *count += 1;
+                }
             }
         }
-        writeln!(f, "")?;
-        for y in 0..self.height {
-            write!(f, "{:2} ", y)?;
-            for x in 0..self.width {
-                match self.display[x][y] {
-                    TileDisplay::Revealed => write!(f, "{}", self.tiles[x][y])?,
-                    TileDisplay::Hidden => write!(f, "| |")?,
-                    TileDisplay::Flag => write!(f, "|!|")?,
-                    TileDisplay::Question => write!(f, "|?|")?,
+        counts
+    }
+
+    /// Pearson's chi-square statistic for mine counts that should be spread evenly over `tiles`.
+    fn chi_square(counts: &[usize], tiles: &[usize]) -> f64 {
+        let total: usize = tiles.iter().map(|&i| counts[i]).sum();
+        let expected = total as f64 / tiles.len() as f64;
+        tiles
+            .iter()
+            .map(|&i| (counts[i] as f64 - expected).powi(2) / expected)
+            .sum()
+    }
+
+    /// The chi-square value with `df` degrees of freedom that a uniform layout only exceeds one
+    /// time in a thousand, using the Wilson-Hilferty approximation.
+    fn critical_value(df: usize) -> f64 {
+        let df = df as f64;
+        let z = 3.090;
+        let k = 2. / (9. * df);
+        df * (1. - k + z * k.sqrt()).powi(3)
+    }
+
+    /// Checks that the counts are spread evenly over `tiles` and that every other tile has none.
+    fn assert_uniform(counts: &[usize], tiles: &[usize]) {
+        for (i, &count) in counts.iter().enumerate() {
+            if !tiles.contains(&i) {
+                assert_eq!(count, 0, "tile {} should never have a mine", i);
+            }
+        }
+        let statistic = chi_square(counts, tiles);
+        let critical = critical_value(tiles.len() - 1);
+        assert!(
+            statistic < critical,
+            "chi-square {:.1} is over {:.1}, so the mines aren't uniform",
+            statistic,
+            critical
+        );
+    }
+
+    fn all_tiles(width: usize, height: usize) -> Vec<usize> {
+        (0..width * height).collect()
+    }
+
+    /// Every tile but (x, y) and, if `around` is set, its neighbours on a plain board.
+    fn tiles_away_from(
+        width: usize,
+        height: usize,
+        x: usize,
+        y: usize,
+        around: bool,
+    ) -> Vec<usize> {
+        let reach = if around { 1 } else { 0 };
+        (0..width * height)
+            .filter(|i| {
+                let (tx, ty) = (i % width, i / width);
+                tx.abs_diff(x) > reach || ty.abs_diff(y) > reach
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chi_square_catches_bias() {
+        let tiles = all_tiles(9, 9);
+        let mut counts = vec![500; tiles.len()];
+        assert!(chi_square(&counts, &tiles) < critical_value(tiles.len() - 1));
+        // A fifth more mines in one corner is the kind of skew relocation could cause
+        for count in counts.iter_mut().take(9) {
+            *count += 100;
+        }
+        assert!(chi_square(&counts, &tiles) > critical_value(tiles.len() - 1));
+    }
+
+    #[test]
+    fn injected_rng_lays_and_relocates_mines() {
+        let builder = BoardBuilder::new(9, 9).mines(30);
+        let mut first = builder.build_with_rng(StdRng::seed_from_u64(7)).unwrap();
+        let mut second = builder.build_with_rng(StdRng::seed_from_u64(7)).unwrap();
+        assert_eq!(first.tiles, second.tiles);
+        first.reveal_at(4, 4).unwrap();
+        second.reveal_at(4, 4).unwrap();
+        assert_eq!(first.tiles, second.tiles);
+
+        // The rng is only ever drawn from, so a seeded build makes the same choices
+        let mut seeded = builder.clone().seed(7).build().unwrap();
+        seeded.reveal_at(4, 4).unwrap();
+        assert_eq!(seeded.tiles, first.tiles);
+        let mut other = builder.build_with_rng(StdRng::seed_from_u64(8)).unwrap();
+        other.reveal_at(4, 4).unwrap();
+        assert_ne!(other.tiles, first.tiles);
+    }
+
+    #[test]
+    fn no_guess_reports_a_board_it_cannot_clear() {
+        let mut board = BoardBuilder::new(9, 9).mines(40).seed(1).build().unwrap();
+        board.first_click = FirstClickPolicy::NoGuess;
+        let tiles = board.tiles.clone();
+        assert_eq!(
+            board.reveal_at(4, 4),
+            Err(BoardError::NoGuessLayout {
+                mines: 40,
+                attempts: NO_GUESS_ATTEMPTS
+            })
+        );
+        assert!(!board.is_started());
+        assert_eq!(board.tiles, tiles);
+        assert_eq!(board.get_display_at(4, 4), Ok(TileDisplay::Hidden));
+    }
+
+    #[test]
+    fn dense_no_guess_boards_are_refused() {
+        let builder = BoardBuilder::new(9, 9)
+            .mines(18)
+            .first_click(FirstClickPolicy::NoGuess);
+        assert_eq!(
+            builder.build().unwrap_err(),
+            BoardError::TooManyMines {
+                mines: 18,
+                max: 17,
+                first_click: FirstClickPolicy::NoGuess
+            }
+        );
+        // The densest board allowed can still be cleared from a corner
+        let mut densest = builder.mines(17).seed(1).build().unwrap();
+        densest.reveal_at(0, 0).unwrap();
+        // Expert boards are just below the limit
+        let expert = BoardBuilder::new(30, 16).first_click(FirstClickPolicy::NoGuess);
+        assert!(expert.max_mines() >= 99);
+    }
+
+    /// Checks that every mine on the board is matched by one on its partner.
+    fn assert_symmetric(board: &Board) {
+        for y in 0..board.height {
+            for x in 0..board.width {
+                if let Some((px, py)) = board.placement.partner(x, y, board.width, board.height) {
+                    assert_eq!(
+                        board.get_tile_at(x, y) == Ok(Tile::Mine),
+                        board.get_tile_at(px, py) == Ok(Tile::Mine),
+                        "({}, {}) doesn't match ({}, {}) on seed {}",
+                        x,
+                        y,
+                        px,
+                        py,
+                        board.seed
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_boards_stay_symmetric_after_the_first_click() {
+        use crate::placement::Symmetric;
+        let boards = [
+            (Symmetric::mirror(), 20, FirstClickPolicy::Opening),
+            (Symmetric::mirror(), 15, FirstClickPolicy::Opening),
+            (Symmetric::mirror(), 31, FirstClickPolicy::SafeOnly),
+            (Symmetric::mirror(), 10, FirstClickPolicy::NoGuess),
+            (Symmetric::rotational(), 20, FirstClickPolicy::Opening),
+            (Symmetric::rotational(), 25, FirstClickPolicy::Unprotected),
+        ];
+        for (placement, mines, first_click) in boards.iter() {
+            for seed in 0..50 {
+                let mut board = BoardBuilder::new(9, 9)
+                    .mines(*mines)
+                    .first_click(*first_click)
+                    .placement(Rc::new(*placement))
+                    .seed(seed)
+                    .build()
+                    .unwrap();
+                assert_symmetric(&board);
+                let (x, y) = (seed as usize % 9, seed as usize * 5 % 9);
+                board.reveal_at(x, y).unwrap();
+                assert_symmetric(&board);
+                let laid = board
+                    .tiles
+                    .iter()
+                    .filter(|&&tile| tile == Tile::Mine)
+                    .count();
+                assert_eq!(laid, *mines);
+            }
+        }
+    }
+
+    #[test]
+    fn unpairable_mine_counts_are_refused() {
+        use crate::placement::Symmetric;
+        // An even width has no middle column for the odd mine
+        let even = BoardBuilder::new(8, 8)
+            .mines(15)
+            .placement(Rc::new(Symmetric::mirror()));
+        assert!(matches!(
+            even.build(),
+            Err(BoardError::UnpairedMine { mines: 15, .. })
+        ));
+        // The only tile a turned board can leave unmatched is in the middle, where the first
+        // click might clear it
+        let rotational = BoardBuilder::new(9, 9)
+            .mines(15)
+            .placement(Rc::new(Symmetric::rotational()));
+        assert!(rotational.build().is_err());
+        let unprotected = rotational.first_click(FirstClickPolicy::Unprotected);
+        assert!(unprotected.build().is_ok());
+    }
+
+    #[test]
+    fn clearing_an_odd_number_of_middle_mines_leaves_room_for_the_odd_one() {
+        use crate::placement::Symmetric;
+        // Every mine could fit in pairs, but one on each middle tile would leave nowhere for the
+        // mine moved off the first click
+        let mut board = BoardBuilder::new(7, 2)
+            .mines(6)
+            .first_click(FirstClickPolicy::SafeOnly)
+            .placement(Rc::new(Symmetric::mirror()))
+            .seed(1)
+            .build()
+            .unwrap();
+        board.reveal_at(3, 0).unwrap();
+        assert_symmetric(&board);
+
+        let builder = BoardBuilder::new(9, 9)
+            .mines(60)
+            .placement(Rc::new(Symmetric::mirror()));
+        for seed in 0..50 {
+            let mut board = builder.clone().seed(seed).build().unwrap();
+            board.reveal_at(4, seed as usize % 9).unwrap();
+            assert_symmetric(&board);
+        }
+    }
+
+    #[test]
+    fn new_boards_are_uniform() {
+        let counts = mine_counts(BoardBuilder::new(9, 9).mines(10), None);
+        assert_uniform(&counts, &all_tiles(9, 9));
+    }
+
+    #[test]
+    fn dense_boards_are_uniform() {
+        let counts = mine_counts(BoardBuilder::new(8, 6).mines(30), None);
+        assert_uniform(&counts, &all_tiles(8, 6));
+    }
+
+    #[test]
+    fn opening_relocation_is_uniform_from_a_corner() {
+        let builder = BoardBuilder::new(9, 9).mines(30);
+        let counts = mine_counts(builder, Some((0, 0)));
+        assert_uniform(&counts, &tiles_away_from(9, 9, 0, 0, true));
+    }
+
+    #[test]
+    fn opening_relocation_is_uniform_from_off_centre() {
+        let builder = BoardBuilder::new(9, 9).mines(30);
+        let counts = mine_counts(builder, Some((2, 5)));
+        assert_uniform(&counts, &tiles_away_from(9, 9, 2, 5, true));
+    }
+
+    #[test]
+    fn safe_only_relocation_is_uniform() {
+        let builder = BoardBuilder::new(9, 9)
+            .mines(30)
+            .first_click(FirstClickPolicy::SafeOnly);
+        let counts = mine_counts(builder, Some((7, 1)));
+        assert_uniform(&counts, &tiles_away_from(9, 9, 7, 1, false));
+    }
+
+    #[test]
+    fn unprotected_first_reveal_moves_nothing() {
+        let builder = BoardBuilder::new(9, 9)
+            .mines(30)
+            .first_click(FirstClickPolicy::Unprotected);
+        let before = mine_counts(builder.clone(), None);
+        let after = mine_counts(builder, Some((4, 4)));
+        assert_eq!(before, after);
+    }
+
+    /// Plays kind boards by always guessing, either at random when nothing is provably safe or
+    /// on purpose when something is, and returns how many of each guess hit a mine.
+    fn kind_guesses(needless: bool) -> (usize, usize) {
+        let mut guesses = 0;
+        let mut hits = 0;
+        for seed in 0..40 {
+            let mut board = BoardBuilder::new(9, 9)
+                .mines(12)
+                .seed(seed)
+                .first_click(FirstClickPolicy::Kind)
+                .build()
+                .unwrap();
+            let mut rng = StdRng::seed_from_u64(seed);
+            board.reveal_at(4, 4).unwrap();
+            while !board.check_victory() && !board.mine_revealed() {
+                let probabilities = crate::probability::mine_probabilities(&board);
+                let mut safe = Vec::new();
+                let mut unsure = Vec::new();
+                for (x, column) in probabilities.iter().enumerate() {
+                    for (y, probability) in column.iter().enumerate() {
+                        match probability {
+                            Some(p) if *p == 0. => safe.push((x, y)),
+                            // Leave out tiles that are only unsure because of rounding
+                            Some(p) if *p < 0.999 => unsure.push((x, y)),
+                            _ => (),
+                        }
+                    }
+                }
+                let guessing = safe.is_empty() || needless;
+                let (x, y) = if guessing && !unsure.is_empty() {
+                    unsure[rng.gen_range(0, unsure.len())]
+                } else {
+                    safe[0]
                 };
+                let outcome = board.reveal_at(x, y).unwrap();
+                if guessing && !unsure.is_empty() && safe.is_empty() != needless {
+                    guesses += 1;
+                    hits += outcome.hit_mine() as usize;
+                }
             }
-            writeln!(f, "")?;
         }
-        write!(f, "")
+        (guesses, hits)
+    }
+
+    #[test]
+    fn kind_boards_never_punish_forced_guesses() {
+        let (guesses, hits) = kind_guesses(false);
+        assert!(guesses > 0);
+        assert_eq!(hits, 0);
+    }
+
+    #[test]
+    fn kind_boards_punish_needless_guesses() {
+        let (guesses, hits) = kind_guesses(true);
+        assert!(guesses > 0);
+        assert_eq!(hits, guesses);
+    }
+
+    #[test]
+    fn kind_settles_are_undone_and_redone() {
+        let mut board = BoardBuilder::new(9, 9)
+            .mines(15)
+            .seed(1)
+            .first_click(FirstClickPolicy::Kind)
+            .build()
+            .unwrap();
+        let mut saves = vec![board.save()];
+        board.reveal_at(4, 4).unwrap();
+        saves.push(board.save());
+        // Guess whenever something is provably safe, until a guess is punished
+        loop {
+            let probabilities = crate::probability::mine_probabilities(&board);
+            let mut safe = None;
+            let mut unsure = None;
+            for (x, column) in probabilities.iter().enumerate() {
+                for (y, probability) in column.iter().enumerate() {
+                    match probability {
+                        Some(p) if *p == 0. => safe = Some((x, y)),
+                        Some(p) if *p < 0.999 => unsure = Some((x, y)),
+                        _ => (),
+                    }
+                }
+            }
+            let (x, y) = match (safe, unsure) {
+                (Some(_), Some(guess)) => guess,
+                (Some(safe), None) => safe,
+                (None, Some(guess)) => guess,
+                (None, None) => panic!("The board was cleared without a guess being punished"),
+            };
+            let outcome = board.reveal_at(x, y).unwrap();
+            saves.push(board.save());
+            if outcome.hit_mine() {
+                break;
+            }
+        }
+        let last = saves.len() - 1;
+        assert!(board.undo());
+        assert_eq!(board.save(), saves[last - 1]);
+        assert!(board.undo());
+        assert_eq!(board.save(), saves[last - 2]);
+        assert!(board.redo());
+        assert_eq!(board.save(), saves[last - 1]);
+        assert!(board.redo());
+        assert_eq!(board.save(), saves[last]);
+        assert!(board.mine_revealed());
+    }
+
+    #[test]
+    fn kind_boards_only_spread_mines_evenly() {
+        use crate::placement::{Clustered, Symmetric};
+        let builder = BoardBuilder::new(9, 9)
+            .mines(10)
+            .first_click(FirstClickPolicy::Kind);
+        assert!(builder.build().is_ok());
+        let placements: [Rc<dyn MinePlacement>; 2] =
+            [Rc::new(Clustered), Rc::new(Symmetric::mirror())];
+        for placement in placements.iter() {
+            assert_eq!(
+                builder
+                    .clone()
+                    .placement(placement.clone())
+                    .build()
+                    .unwrap_err(),
+                BoardError::KindPlacement {
+                    placement: placement.name()
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn shaped_boards_are_uniform_inside_the_shape() {
+        let shape = Shape::circle(9, 9);
+        let inside: Vec<usize> = (0..81).filter(|i| shape.contains(i % 9, i / 9)).collect();
+        let counts = mine_counts(BoardBuilder::new(9, 9).shape(shape).mines(15), Some((4, 4)));
+        let away = tiles_away_from(9, 9, 4, 4, true);
+        let allowed: Vec<usize> = inside.into_iter().filter(|i| away.contains(i)).collect();
+        assert_uniform(&counts, &allowed);
+    }
+
+    #[test]
+    fn saved_numbers_have_to_match_their_mines() {
+        let save = |tiles: &str| {
+            format!(
+                "{} {}\n3,2,1,0,true,opening,square,false,uniform\n{}\nRHR\nRRR\n",
+                SAVE_HEADER, SAVE_VERSION, tiles
+            )
+        };
+        let board = Board::load(&save("1 * 1\n1 1 1")).unwrap();
+        assert_eq!(board.get_tile_at(1, 0), Ok(Tile::Mine));
+        assert!(matches!(
+            Board::load(&save("1 * 0\n1 1 0")),
+            Err(BoardError::InvalidSave(_))
+        ));
     }
 }
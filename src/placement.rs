@@ -0,0 +1,154 @@
+use crate::minesweeper::{Board, Tile};
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// How strongly a mine pulls more mines onto the tiles around it on a clustered board.
+const CLUSTER_PULL: f64 = 4.;
+
+/// How likely the sparsest edge of a gradient board is to get a mine, compared with the densest.
+const GRADIENT_LOW: f64 = 0.1;
+
+/// How mines are spread over a board as it is laid out. Tiles are picked at random and each is
+/// kept with a chance of its weight over the largest weight, so tiles with larger weights end up
+/// with more mines.
+pub trait MinePlacement: Debug {
+    /// The name the placement is saved under, as read by `placement_from_name`.
+    fn name(&self) -> String;
+
+    /// How likely (x, y) is to get the next mine, given the mines already on the board.
+    fn weight(&self, _board: &Board, _x: usize, _y: usize) -> f64 {
+        1.
+    }
+
+    /// The largest weight any tile on the board can have.
+    fn max_weight(&self, _board: &Board) -> f64 {
+        1.
+    }
+
+    /// The tile that always gets a mine along with (x, y), and loses it along with (x, y) when
+    /// mines are moved, if there is one. Partners have to go both ways.
+    fn partner(
+        &self,
+        _x: usize,
+        _y: usize,
+        _width: usize,
+        _height: usize,
+    ) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Whether every tile is always equally likely to get a mine and no tile has a partner. Kind
+    /// boards settle their hidden tiles without regard to weights or partners, so they can only
+    /// keep to a placement like this.
+    fn is_uniform(&self) -> bool {
+        false
+    }
+}
+
+/// Every tile is equally likely to get a mine.
+#[derive(Debug, Copy, Clone)]
+pub struct Uniform;
+
+impl MinePlacement for Uniform {
+    fn name(&self) -> String {
+        "uniform".to_owned()
+    }
+
+    fn is_uniform(&self) -> bool {
+        true
+    }
+}
+
+/// Tiles next to mines are more likely to get mines, so they grow into blobs.
+#[derive(Debug, Copy, Clone)]
+pub struct Clustered;
+
+impl MinePlacement for Clustered {
+    fn name(&self) -> String {
+        "clustered".to_owned()
+    }
+
+    fn weight(&self, board: &Board, x: usize, y: usize) -> f64 {
+        let mines = board
+            .neighbours(x, y)
+            .into_iter()
+            .filter(|&(nx, ny)| board.get_tile_at(nx, ny) == Ok(Tile::Mine))
+            .count();
+        1. + CLUSTER_PULL * mines as f64
+    }
+
+    fn max_weight(&self, board: &Board) -> f64 {
+        1. + CLUSTER_PULL * board.most_neighbours() as f64
+    }
+}
+
+/// Mines get steadily denser from the left edge of the board to the right.
+#[derive(Debug, Copy, Clone)]
+pub struct Gradient;
+
+impl MinePlacement for Gradient {
+    fn name(&self) -> String {
+        "gradient".to_owned()
+    }
+
+    fn weight(&self, board: &Board, x: usize, _y: usize) -> f64 {
+        let across = if board.width > 1 {
+            x as f64 / (board.width - 1) as f64
+        } else {
+            1.
+        };
+        GRADIENT_LOW + (1. - GRADIENT_LOW) * across
+    }
+}
+
+/// Every mine is matched by one on the opposite side of the board, either reflected left to
+/// right or turned half way around the middle. Only a tile on the middle line, which is its own
+/// reflection, can have a mine without a match.
+#[derive(Debug, Copy, Clone)]
+pub struct Symmetric {
+    rotational: bool,
+}
+
+impl Symmetric {
+    pub fn mirror() -> Symmetric {
+        Symmetric { rotational: false }
+    }
+
+    pub fn rotational() -> Symmetric {
+        Symmetric { rotational: true }
+    }
+}
+
+impl MinePlacement for Symmetric {
+    fn name(&self) -> String {
+        if self.rotational {
+            "rotational".to_owned()
+        } else {
+            "mirror".to_owned()
+        }
+    }
+
+    fn partner(&self, x: usize, y: usize, width: usize, height: usize) -> Option<(usize, usize)> {
+        let partner = if self.rotational {
+            (width - 1 - x, height - 1 - y)
+        } else {
+            (width - 1 - x, y)
+        };
+        Some(partner).filter(|&partner| partner != (x, y))
+    }
+}
+
+/// The names of every placement, in the order the frontends offer them.
+pub const PLACEMENT_NAMES: [&str; 5] = ["uniform", "clustered", "gradient", "mirror", "rotational"];
+
+/// Returns the placement saved under `name` by `MinePlacement::name`.
+pub fn placement_from_name(name: &str) -> Option<Rc<dyn MinePlacement>> {
+    match name {
+        "uniform" => Some(Rc::new(Uniform)),
+        "clustered" => Some(Rc::new(Clustered)),
+        "gradient" => Some(Rc::new(Gradient)),
+        "mirror" => Some(Rc::new(Symmetric::mirror())),
+        "rotational" => Some(Rc::new(Symmetric::rotational())),
+        _ => None,
+    }
+}
@@ -0,0 +1,385 @@
+use crate::minesweeper::*;
+use crate::solver::{Constraint, Knowledge, View};
+use std::collections::{HashMap, HashSet};
+
+/// The chance that each tile is a mine, indexed `[x][y]` like the board.
+/// Revealed tiles and tiles outside the board's shape don't have a probability.
+pub type Probabilities = Vec<Vec<Option<f64>>>;
+
+/// Computes the exact probability that each unrevealed tile is a mine, given only what is
+/// visible on the board. Every arrangement of mines that agrees with the revealed numbers and the
+/// total number of mines is treated as equally likely.
+pub fn mine_probabilities(board: &Board) -> Probabilities {
+    let mut view = View::new(board);
+    // Anything that can be proven doesn't need to be enumerated
+    view.solve();
+    let mut probabilities = vec![vec![None; board.height]; board.width];
+    for (x, column) in probabilities.iter_mut().enumerate() {
+        for (y, probability) in column.iter_mut().enumerate() {
+            if board.get_display_at(x, y) == Ok(TileDisplay::Revealed)
+                || board.get_tile_at(x, y) == Ok(Tile::Void)
+            {
+                continue;
+            }
+            *probability = match view.known_at(x, y) {
+                Knowledge::Safe => Some(0.),
+                Knowledge::Mine => Some(1.),
+                Knowledge::Unknown => None,
+            };
+        }
+    }
+
+    let mines_left = view.mines_left();
+    let constraints = view.constraints();
+    let groups: Vec<Group> = split_groups(&constraints)
+        .into_iter()
+        .map(|group| Group::enumerate(&group, mines_left))
+        .collect();
+    let frontier: HashSet<(usize, usize)> = groups
+        .iter()
+        .flat_map(|group| group.cells.iter().cloned())
+        .collect();
+    let interior: Vec<(usize, usize)> = view
+        .unknown_cells()
+        .into_iter()
+        .filter(|cell| !frontier.contains(cell))
+        .collect();
+
+    // weights[t] is proportional to the number of ways to place the mines that aren't in the
+    // frontier when the frontier holds t of them
+    let total = groups
+        .iter()
+        .fold(vec![1.], |total, group| convolve(&total, &group.ways));
+    let weights = interior_weights(interior.len(), mines_left, total.len());
+    let possible: f64 = total.iter().zip(weights.iter()).map(|(n, w)| n * w).sum();
+
+    if !interior.is_empty() {
+        let interior_mines: f64 = total
+            .iter()
+            .zip(weights.iter())
+            .enumerate()
+            .map(|(t, (n, w))| n * w * (mines_left.saturating_sub(t)) as f64)
+            .sum();
+        let probability = interior_mines / (interior.len() as f64) / possible;
+        for (x, y) in interior {
+            probabilities[x][y] = Some(probability);
+        }
+    }
+    for (i, group) in groups.iter().enumerate() {
+        let others = groups
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .fold(vec![1.], |total, (_, other)| convolve(&total, &other.ways));
+        for (cell, mine_ways) in group.cells.iter().zip(group.mine_ways.iter()) {
+            let mut mines = 0.;
+            for (k, ways) in mine_ways.iter().enumerate() {
+                for (j, other_ways) in others.iter().enumerate() {
+                    mines += ways * other_ways * weights[k + j];
+                }
+            }
+            probabilities[cell.0][cell.1] = Some(mines / possible);
+        }
+    }
+    probabilities
+}
+
+/// A set of frontier tiles that share no constraints with tiles outside of it.
+#[derive(Debug)]
+struct Group {
+    cells: Vec<(usize, usize)>,
+    /// `ways[k]` is proportional to the number of valid arrangements with k mines
+    ways: Vec<f64>,
+    /// `mine_ways[i][k]` is proportional to the number of those arrangements where `cells[i]` is
+    /// a mine
+    mine_ways: Vec<Vec<f64>>,
+}
+
+impl Group {
+    fn enumerate(constraints: &[&Constraint], max_mines: usize) -> Group {
+        let frontier = Frontier::new(constraints);
+        let cells = frontier.cells.len();
+        let mut search = Search {
+            frontier,
+            max_mines,
+            ways: vec![0.; cells + 1],
+            mine_ways: vec![vec![0.; cells + 1]; cells],
+        };
+        search.search(0, 0);
+
+        // Only the ratios matter, so keep the numbers small enough to multiply together
+        let scale = search.ways.iter().cloned().fold(0., f64::max);
+        if scale > 0. {
+            for ways in search.ways.iter_mut() {
+                *ways /= scale;
+            }
+            for ways in search.mine_ways.iter_mut().flat_map(|w| w.iter_mut()) {
+                *ways /= scale;
+            }
+        }
+        Group {
+            cells: search.frontier.cells,
+            ways: search.ways,
+            mine_ways: search.mine_ways,
+        }
+    }
+}
+
+/// The tiles a set of constraints covers, with what a backtracking search needs to check the
+/// constraints as each tile is decided.
+pub struct Frontier {
+    /// Tiles in the order the constraints reach them, so that each constraint is filled in soon
+    /// after it is first touched and bad arrangements are dropped early
+    pub cells: Vec<(usize, usize)>,
+    index: HashMap<(usize, usize), usize>,
+    cell_constraints: Vec<Vec<usize>>,
+    targets: Vec<usize>,
+    mines_in: Vec<usize>,
+    unassigned: Vec<usize>,
+    /// Whether each tile in `cells` is a mine, for the tiles decided so far
+    pub assignment: Vec<bool>,
+}
+
+impl Frontier {
+    pub fn new(constraints: &[&Constraint]) -> Frontier {
+        let mut cells = Vec::new();
+        let mut index: HashMap<(usize, usize), usize> = HashMap::new();
+        for constraint in constraints.iter() {
+            for cell in constraint.cells.iter() {
+                if !index.contains_key(cell) {
+                    index.insert(*cell, cells.len());
+                    cells.push(*cell);
+                }
+            }
+        }
+        let mut cell_constraints = vec![Vec::new(); cells.len()];
+        for (c, constraint) in constraints.iter().enumerate() {
+            for cell in constraint.cells.iter() {
+                cell_constraints[index[cell]].push(c);
+            }
+        }
+        Frontier {
+            cell_constraints,
+            targets: constraints.iter().map(|c| c.mines).collect(),
+            mines_in: vec![0; constraints.len()],
+            unassigned: constraints.iter().map(|c| c.cells.len()).collect(),
+            assignment: vec![false; cells.len()],
+            cells,
+            index,
+        }
+    }
+
+    pub fn contains(&self, cell: &(usize, usize)) -> bool {
+        self.index.contains_key(cell)
+    }
+
+    /// Decides whether `cells[cell]` is a mine, as long as every constraint on it can still be
+    /// met. Returns false, leaving it undecided, if one can't.
+    pub fn assign(&mut self, cell: usize, mine: bool) -> bool {
+        let added = mine as usize;
+        let fits = self.cell_constraints[cell].iter().all(|&c| {
+            let placed = self.mines_in[c] + added;
+            // There must still be enough unassigned tiles, not counting this one, for the rest
+            placed <= self.targets[c] && placed + self.unassigned[c] > self.targets[c]
+        });
+        if !fits {
+            return false;
+        }
+        for &c in self.cell_constraints[cell].iter() {
+            self.mines_in[c] += added;
+            self.unassigned[c] -= 1;
+        }
+        self.assignment[cell] = mine;
+        true
+    }
+
+    /// Takes back the last `assign` of `cells[cell]`.
+    pub fn unassign(&mut self, cell: usize) {
+        let added = self.assignment[cell] as usize;
+        for &c in self.cell_constraints[cell].iter() {
+            self.mines_in[c] -= added;
+            self.unassigned[c] += 1;
+        }
+        self.assignment[cell] = false;
+    }
+}
+
+/// Backtracking search over every arrangement of mines in a group.
+struct Search {
+    frontier: Frontier,
+    max_mines: usize,
+    ways: Vec<f64>,
+    mine_ways: Vec<Vec<f64>>,
+}
+
+impl Search {
+    fn search(&mut self, cell: usize, mines: usize) {
+        if cell == self.frontier.cells.len() {
+            self.ways[mines] += 1.;
+            for (i, mine) in self.frontier.assignment.iter().enumerate() {
+                if *mine {
+                    self.mine_ways[i][mines] += 1.;
+                }
+            }
+            return;
+        }
+        for &mine in [false, true].iter() {
+            let added = mine as usize;
+            if mines + added > self.max_mines || !self.frontier.assign(cell, mine) {
+                continue;
+            }
+            self.search(cell + 1, mines + added);
+            self.frontier.unassign(cell);
+        }
+    }
+}
+
+/// Splits the constraints into groups that don't share any tiles. The constraints of each group
+/// are listed in the order they are reached from the group's first constraint.
+fn split_groups(constraints: &[Constraint]) -> Vec<Vec<&Constraint>> {
+    let mut by_cell: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (i, constraint) in constraints.iter().enumerate() {
+        for cell in constraint.cells.iter() {
+            by_cell.entry(*cell).or_default().push(i);
+        }
+    }
+    let mut visited = vec![false; constraints.len()];
+    let mut groups = Vec::new();
+    for start in 0..constraints.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut queue = vec![start];
+        let mut group = Vec::new();
+        while let Some(c) = queue.pop() {
+            group.push(&constraints[c]);
+            for cell in constraints[c].cells.iter() {
+                for &next in by_cell[cell].iter() {
+                    if !visited[next] {
+                        visited[next] = true;
+                        queue.push(next);
+                    }
+                }
+            }
+        }
+        groups.push(group);
+    }
+    groups
+}
+
+/// For every number of mines the frontier could hold, a weight proportional to the number of ways
+/// of placing the rest of the mines among the `interior` tiles.
+fn interior_weights(interior: usize, mines_left: usize, frontier_counts: usize) -> Vec<f64> {
+    let mut ln_factorial = vec![0.; interior + 1];
+    for i in 1..=interior {
+        ln_factorial[i] = ln_factorial[i - 1] + (i as f64).ln();
+    }
+    let ln_choose: Vec<Option<f64>> = (0..frontier_counts)
+        .map(|t| {
+            if t > mines_left || mines_left - t > interior {
+                return None;
+            }
+            let k = mines_left - t;
+            Some(ln_factorial[interior] - ln_factorial[k] - ln_factorial[interior - k])
+        })
+        .collect();
+    let max = ln_choose
+        .iter()
+        .filter_map(|w| *w)
+        .fold(f64::NEG_INFINITY, f64::max);
+    ln_choose
+        .into_iter()
+        .map(|w| w.map_or(0., |w| (w - max).exp()))
+        .collect()
+}
+
+fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut result = vec![0.; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            result[i + j] += x * y;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts every way of laying the board's mines on its hidden tiles that fits the revealed
+    /// numbers, and how often each tile gets a mine.
+    fn enumerate(board: &Board) -> Probabilities {
+        let hidden: Vec<(usize, usize)> = (0..board.width)
+            .flat_map(|x| (0..board.height).map(move |y| (x, y)))
+            .filter(|&(x, y)| board.get_display_at(x, y) != Ok(TileDisplay::Revealed))
+            .collect();
+        let mut ways = 0;
+        let mut mine_ways = vec![0; hidden.len()];
+        for layout in 0..1u32 << hidden.len() {
+            if layout.count_ones() as usize != board.mines {
+                continue;
+            }
+            let is_mine = |cell: &(usize, usize)| {
+                hidden
+                    .iter()
+                    .position(|tile| tile == cell)
+                    .is_some_and(|i| layout & 1 << i != 0)
+            };
+            let fits = (0..board.width)
+                .flat_map(|x| (0..board.height).map(move |y| (x, y)))
+                .all(|(x, y)| match board.get_display_at(x, y) {
+                    Ok(TileDisplay::Revealed) => {
+                        let mines = board.neighbours(x, y).iter().filter(|n| is_mine(n)).count();
+                        board.get_tile_at(x, y) == Ok(Tile::Safe(Count(mines)))
+                    }
+                    _ => true,
+                });
+            if fits {
+                ways += 1;
+                for (i, count) in mine_ways.iter_mut().enumerate() {
+                    *count += (layout >> i & 1) as usize;
+                }
+            }
+        }
+        let mut probabilities = vec![vec![None; board.height]; board.width];
+        for (&(x, y), count) in hidden.iter().zip(mine_ways.iter()) {
+            probabilities[x][y] = Some(*count as f64 / ways as f64);
+        }
+        probabilities
+    }
+
+    #[test]
+    fn probabilities_match_every_layout_counted_out() {
+        let boards: [&[&str]; 3] = [
+            // Numbers that share tiles, with a column away from them
+            &["#*###", "*..##", "####*"],
+            // Two separate groups of numbers
+            &["*#.#*", "#...#", "#.#.#", "##*##"],
+            // A pair of tiles the numbers can't tell apart
+            &["*#", "..", "##"],
+        ];
+        for rows in boards.iter() {
+            let board = Board::from_rows(rows);
+            let expected = enumerate(&board);
+            let found = mine_probabilities(&board);
+            for x in 0..board.width {
+                for y in 0..board.height {
+                    match (found[x][y], expected[x][y]) {
+                        (Some(found), Some(expected)) => assert!(
+                            (found - expected).abs() < 1e-9,
+                            "({}, {}) of {:?} is {} but should be {}",
+                            x,
+                            y,
+                            rows,
+                            found,
+                            expected
+                        ),
+                        (found, expected) => assert_eq!(found, expected),
+                    }
+                }
+            }
+        }
+    }
+}
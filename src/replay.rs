@@ -0,0 +1,196 @@
+use crate::minesweeper::*;
+use std::fmt::{Display, Formatter};
+
+/// Version of the text format written by `Replay::save`.
+const REPLAY_VERSION: u32 = 1;
+const REPLAY_HEADER: &str = "minesweeper replay";
+
+/// A single call a frontend made on its board.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Action {
+    Reveal(usize, usize),
+    /// Cycles the tile between hidden, flagged and questioned
+    Toggle(usize, usize),
+    Chord(usize, usize),
+    RevealAll,
+    Undo,
+    Redo,
+}
+
+impl Action {
+    /// Makes the same call on the board that the frontend made when the action was recorded.
+    pub fn apply(&self, board: &mut Board) -> Result<(), BoardError> {
+        match *self {
+            Action::Reveal(x, y) => board.reveal_at(x, y).map(|_| ()),
+            Action::Toggle(x, y) => board.toggle_display_at(x, y).map(|_| ()),
+            Action::Chord(x, y) => board.chord(x, y).map(|_| ()),
+            Action::RevealAll => {
+                board.reveal_all();
+                Ok(())
+            }
+            Action::Undo => {
+                board.undo();
+                Ok(())
+            }
+            Action::Redo => {
+                board.redo();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Reveal(x, y) => write!(f, "reveal {} {}", x, y),
+            Action::Toggle(x, y) => write!(f, "toggle {} {}", x, y),
+            Action::Chord(x, y) => write!(f, "chord {} {}", x, y),
+            Action::RevealAll => write!(f, "reveal_all"),
+            Action::Undo => write!(f, "undo"),
+            Action::Redo => write!(f, "redo"),
+        }
+    }
+}
+
+/// An action and the number of seconds into the game it was made.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Step {
+    pub time: f64,
+    pub action: Action,
+}
+
+/// A recording of a game: the board as it was when recording started, every action made on it,
+/// and the board as it was when the game finished.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    start: String,
+    pub steps: Vec<Step>,
+    finish: Option<String>,
+}
+
+impl Replay {
+    /// Starts recording a game on the board. The board's seed is saved along with its layout, so
+    /// the mines are moved the same way when the first tile is revealed during playback.
+    pub fn new(board: &Board) -> Replay {
+        Replay {
+            start: board.save(),
+            steps: Vec::new(),
+            finish: None,
+        }
+    }
+
+    pub fn record(&mut self, time: f64, action: Action) {
+        self.steps.push(Step { time, action });
+    }
+
+    /// Stores the final state of the board so playback can be checked against it.
+    pub fn finish(&mut self, board: &Board) {
+        self.finish = Some(board.save());
+    }
+
+    /// Returns a copy of the board as it was when recording started.
+    pub fn start_board(&self) -> Result<Board, BoardError> {
+        Board::load(&self.start)
+    }
+
+    /// Plays every step on a fresh board and checks that it ends up the way it did when the game
+    /// was recorded. Returns the final board.
+    pub fn play(&self) -> Result<Board, String> {
+        let mut board = self.start_board().map_err(|e| e.to_string())?;
+        for (i, step) in self.steps.iter().enumerate() {
+            step.action
+                .apply(&mut board)
+                .map_err(|e| format!("Step {} ({}) failed: {}", i + 1, step.action, e))?;
+        }
+        match &self.finish {
+            Some(finish) if *finish != board.save() => {
+                Err("The replay did not end with the recorded board".to_owned())
+            }
+            _ => Ok(board),
+        }
+    }
+
+    /// Writes the replay to a versioned text format. After the header comes one step per line,
+    /// then the starting board and, if the game finished, the final board, separated by blank
+    /// lines.
+    pub fn save(&self) -> String {
+        let mut save = format!("{} {}\n", REPLAY_HEADER, REPLAY_VERSION);
+        for step in self.steps.iter() {
+            save.push_str(&format!("{:.3} {}\n", step.time, step.action));
+        }
+        save.push('\n');
+        save.push_str(&self.start);
+        if let Some(finish) = &self.finish {
+            save.push('\n');
+            save.push_str(finish);
+        }
+        save
+    }
+
+    /// Reads a replay written by `Replay::save`.
+    pub fn load(save: &str) -> Result<Replay, String> {
+        let mut sections = save.split("\n\n");
+        let mut lines = sections.next().ok_or("The replay is empty")?.lines();
+        let header = lines.next().ok_or("The replay is empty")?;
+        let version: u32 = header
+            .strip_prefix(REPLAY_HEADER)
+            .and_then(|version| version.trim().parse().ok())
+            .ok_or(format!("Not a replay: {}", header))?;
+        if version != REPLAY_VERSION {
+            return Err(format!(
+                "Replay has version {}, only version {} can be played",
+                version, REPLAY_VERSION
+            ));
+        }
+        let mut steps = Vec::new();
+        for line in lines {
+            steps.push(parse_step(line).ok_or(format!("Invalid step in replay: {}", line))?);
+        }
+        // Splitting the sections drops the line break at the end of each board
+        let start = format!(
+            "{}\n",
+            sections
+                .next()
+                .ok_or("The replay has no starting board")?
+                .trim_end()
+        );
+        // Check the board now rather than when the replay is played
+        Board::load(&start).map_err(|e| e.to_string())?;
+        let finish = sections
+            .next()
+            .map(|finish| format!("{}\n", finish.trim_end()));
+        Ok(Replay {
+            start,
+            steps,
+            finish,
+        })
+    }
+}
+
+fn parse_step(line: &str) -> Option<Step> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let time: f64 = words.first()?.parse().ok()?;
+    let coordinates = || -> Option<(usize, usize)> {
+        Some((words.get(2)?.parse().ok()?, words.get(3)?.parse().ok()?))
+    };
+    let action = match *words.get(1)? {
+        "reveal" => {
+            let (x, y) = coordinates()?;
+            Action::Reveal(x, y)
+        }
+        "toggle" => {
+            let (x, y) = coordinates()?;
+            Action::Toggle(x, y)
+        }
+        "chord" => {
+            let (x, y) = coordinates()?;
+            Action::Chord(x, y)
+        }
+        "reveal_all" => Action::RevealAll,
+        "undo" => Action::Undo,
+        "redo" => Action::Redo,
+        _ => return None,
+    };
+    Some(Step { time, action })
+}
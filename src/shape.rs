@@ -0,0 +1,96 @@
+/// Which cells of a `width` by `height` rectangle are part of a board. Cells outside the shape
+/// are never mines, never count towards the numbers around them and are not drawn.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Shape {
+    width: usize,
+    height: usize,
+    /// Stored row by row, like the tiles of a board
+    cells: Vec<bool>,
+}
+
+impl Shape {
+    /// Makes a shape from whether each cell is part of it, row by row.
+    pub fn from_cells(width: usize, height: usize, cells: Vec<bool>) -> Shape {
+        assert_eq!(cells.len(), width * height, "A shape needs every cell");
+        Shape {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /// Reads a shape drawn in text, one line per row. Spaces and dots are left out of the board
+    /// and any other character is part of it. Rows shorter than the longest are padded with
+    /// cells that are left out.
+    pub fn from_template(template: &str) -> Shape {
+        let rows: Vec<Vec<char>> = template
+            .trim_end()
+            .lines()
+            .map(|line| line.trim_end_matches('\r').chars().collect())
+            .collect();
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut cells = Vec::with_capacity(width * rows.len());
+        for row in rows.iter() {
+            for x in 0..width {
+                cells.push(row.get(x).is_some_and(|&c| c != ' ' && c != '.'));
+            }
+        }
+        Shape::from_cells(width, rows.len(), cells)
+    }
+
+    /// The largest ellipse that fits in the rectangle.
+    pub fn circle(width: usize, height: usize) -> Shape {
+        Shape::from_fn(width, height, |u, v| u * u + v * v <= 1.)
+    }
+
+    /// A circle with a hole through the middle.
+    pub fn donut(width: usize, height: usize) -> Shape {
+        Shape::from_fn(width, height, |u, v| {
+            let distance = u * u + v * v;
+            (0.2..=1.).contains(&distance)
+        })
+    }
+
+    /// A heart with its point at the bottom.
+    pub fn heart(width: usize, height: usize) -> Shape {
+        Shape::from_fn(width, height, |u, v| {
+            // The heart curve (x^2 + y^2 - 1)^3 = x^2 y^3, scaled to fill the rectangle
+            let (x, y) = (u * 1.2, 0.125 - v * 1.125);
+            (x * x + y * y - 1.).powi(3) <= x * x * y.powi(3)
+        })
+    }
+
+    /// Repeats the shape down the rows `layers` times, for each layer of a 3D board.
+    pub fn stacked(&self, layers: usize) -> Shape {
+        Shape::from_cells(self.width, self.height * layers, self.cells.repeat(layers))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns true if (x, y) is part of the shape.
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.cells[y * self.width + x]
+    }
+
+    /// The number of cells in the shape.
+    pub fn cells(&self) -> usize {
+        self.cells.iter().filter(|&&cell| cell).count()
+    }
+
+    /// Makes a shape from a test on the middle of each cell, scaled so the rectangle runs from -1
+    /// to 1 both ways.
+    fn from_fn(width: usize, height: usize, inside: impl Fn(f64, f64) -> bool) -> Shape {
+        let scale = |i: usize, size: usize| (i as f64 + 0.5) / size as f64 * 2. - 1.;
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| inside(scale(x, width), scale(y, height)))
+            .collect();
+        Shape::from_cells(width, height, cells)
+    }
+}
@@ -0,0 +1,387 @@
+use crate::minesweeper::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// What the solver knows about a single tile.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Knowledge {
+    Unknown,
+    Safe,
+    Mine,
+}
+
+/// The reasoning used to reach a deduction.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Rule {
+    /// A number already has all of its mines, or has exactly as many unknown neighbours as mines
+    Single,
+    /// Comparing two numbers that share some of their unknown neighbours
+    Overlap,
+    /// Comparing the numbers with the count of mines left on the board
+    MineCount,
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rule::Single => write!(f, "single number"),
+            Rule::Overlap => write!(f, "overlapping numbers"),
+            Rule::MineCount => write!(f, "mine count"),
+        }
+    }
+}
+
+/// A tile that is provably safe or provably a mine, along with the revealed tiles that prove it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Deduction {
+    pub x: usize,
+    pub y: usize,
+    pub mine: bool,
+    pub rule: Rule,
+    pub sources: Vec<(usize, usize)>,
+}
+
+impl Deduction {
+    /// Writes out the deduction with each tile written by `tile`, for frontends that number
+    /// tiles differently from the board.
+    pub fn describe(&self, tile: impl Fn(usize, usize) -> String) -> String {
+        let result = if self.mine { "a mine" } else { "safe" };
+        let mut description = format!("{} is {} by {}", tile(self.x, self.y), result, self.rule);
+        if !self.sources.is_empty() {
+            description.push_str(" from");
+            for &(x, y) in self.sources.iter() {
+                description.push(' ');
+                description.push_str(&tile(x, y));
+            }
+        }
+        description
+    }
+}
+
+impl Display for Deduction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.describe(|x, y| format!("({}, {})", x, y)))
+    }
+}
+
+/// A set of unknown tiles that must contain exactly `mines` mines.
+/// `sources` are the revealed tiles the constraint was built from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Constraint {
+    pub cells: Vec<(usize, usize)>,
+    pub mines: usize,
+    pub sources: Vec<(usize, usize)>,
+}
+
+/// The part of a board that a player can see: revealed numbers, plus anything deduced since.
+/// Flags and question marks are player guesses, so they are treated as unknown tiles.
+#[derive(Debug, Clone)]
+pub struct View<'a> {
+    board: &'a Board,
+    known: Vec<Vec<Knowledge>>,
+    numbers: Vec<Vec<Option<usize>>>,
+    known_mines: usize,
+}
+
+impl<'a> View<'a> {
+    pub fn new(board: &'a Board) -> View<'a> {
+        let mut known = vec![vec![Knowledge::Unknown; board.height]; board.width];
+        let mut numbers = vec![vec![None; board.height]; board.width];
+        let mut known_mines = 0;
+        for x in 0..board.width {
+            for y in 0..board.height {
+                // Tiles outside the board's shape can never be mines
+                if board.get_tile_at(x, y) == Ok(Tile::Void) {
+                    known[x][y] = Knowledge::Safe;
+                    continue;
+                }
+                if board.get_display_at(x, y) != Ok(TileDisplay::Revealed) {
+                    continue;
+                }
+                // Only tiles the player has revealed are ever looked at
+                match board.get_tile_at(x, y).unwrap() {
+                    Tile::Safe(Count(count)) => {
+                        known[x][y] = Knowledge::Safe;
+                        numbers[x][y] = Some(count);
+                    }
+                    Tile::Mine => {
+                        known[x][y] = Knowledge::Mine;
+                        known_mines += 1;
+                    }
+                    Tile::Void => (),
+                }
+            }
+        }
+        View {
+            board,
+            known,
+            numbers,
+            known_mines,
+        }
+    }
+
+    pub fn known_at(&self, x: usize, y: usize) -> Knowledge {
+        self.known[x][y]
+    }
+
+    /// Records a deduction about an unknown tile.
+    pub fn mark(&mut self, x: usize, y: usize, mine: bool) {
+        if self.known[x][y] != Knowledge::Unknown {
+            return;
+        }
+        if mine {
+            self.known[x][y] = Knowledge::Mine;
+            self.known_mines += 1;
+        } else {
+            self.known[x][y] = Knowledge::Safe;
+        }
+    }
+
+    /// The number of mines that are not yet known.
+    pub fn mines_left(&self) -> usize {
+        self.board.mines - self.known_mines
+    }
+
+    pub fn unknown_cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for x in 0..self.board.width {
+            for y in 0..self.board.height {
+                if self.known[x][y] == Knowledge::Unknown {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Builds one constraint for every revealed number that still has unknown neighbours.
+    pub fn constraints(&self) -> Vec<Constraint> {
+        let mut constraints = Vec::new();
+        for x in 0..self.board.width {
+            for y in 0..self.board.height {
+                let number = match self.numbers[x][y] {
+                    Some(number) => number,
+                    None => continue,
+                };
+                let mut cells = Vec::new();
+                let mut mines = number;
+                for (nx, ny) in self.board.neighbours(x, y) {
+                    match self.known[nx][ny] {
+                        Knowledge::Unknown => cells.push((nx, ny)),
+                        Knowledge::Mine => mines -= 1,
+                        Knowledge::Safe => (),
+                    }
+                }
+                if !cells.is_empty() {
+                    cells.sort_unstable();
+                    constraints.push(Constraint {
+                        cells,
+                        mines,
+                        sources: vec![(x, y)],
+                    });
+                }
+            }
+        }
+        constraints
+    }
+
+    /// Finds every tile that can be proven safe or a mine from the current knowledge without
+    /// combining more than two constraints. Does not record the results.
+    pub fn deduce(&self) -> Vec<Deduction> {
+        let mut found = Found::default();
+        let constraints = self.constraints();
+        for constraint in constraints.iter() {
+            if constraint.mines == 0 {
+                found.add_all(&constraint.cells, false, Rule::Single, &constraint.sources);
+            } else if constraint.mines == constraint.cells.len() {
+                found.add_all(&constraint.cells, true, Rule::Single, &constraint.sources);
+            }
+        }
+        if !found.deductions.is_empty() {
+            return found.deductions;
+        }
+
+        let mut by_cell: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (i, constraint) in constraints.iter().enumerate() {
+            for cell in constraint.cells.iter() {
+                by_cell.entry(*cell).or_default().push(i);
+            }
+        }
+        for (i, a) in constraints.iter().enumerate() {
+            let mut partners: Vec<usize> = a
+                .cells
+                .iter()
+                .flat_map(|cell| by_cell[cell].iter().cloned())
+                .filter(|j| *j != i)
+                .collect();
+            partners.sort_unstable();
+            partners.dedup();
+            for j in partners {
+                found.compare(a, &constraints[j], Rule::Overlap);
+            }
+        }
+        if !found.deductions.is_empty() {
+            return found.deductions;
+        }
+
+        let all = Constraint {
+            cells: self.unknown_cells(),
+            mines: self.mines_left(),
+            sources: Vec::new(),
+        };
+        if all.mines == 0 {
+            found.add_all(&all.cells, false, Rule::MineCount, &[]);
+        } else if all.mines == all.cells.len() {
+            found.add_all(&all.cells, true, Rule::MineCount, &[]);
+        } else {
+            for constraint in constraints.iter() {
+                found.compare(&all, constraint, Rule::MineCount);
+                found.compare(constraint, &all, Rule::MineCount);
+            }
+        }
+        found.deductions
+    }
+
+    /// Deduces and records tiles until nothing more can be proven. Returns everything deduced.
+    pub fn solve(&mut self) -> Vec<Deduction> {
+        let mut deductions = Vec::new();
+        loop {
+            let found = self.deduce();
+            if found.is_empty() {
+                break;
+            }
+            for deduction in found.iter() {
+                self.mark(deduction.x, deduction.y, deduction.mine);
+            }
+            deductions.extend(found);
+        }
+        deductions
+    }
+}
+
+/// Finds every tile that can be proven safe or a mine from what is visible on the board,
+/// applying the deductions one after another until nothing more can be proven.
+pub fn solve(board: &Board) -> Vec<Deduction> {
+    View::new(board).solve()
+}
+
+/// Deductions found in a single pass, with each tile appearing at most once.
+#[derive(Default)]
+struct Found {
+    deductions: Vec<Deduction>,
+    cells: HashSet<(usize, usize)>,
+}
+
+impl Found {
+    fn add_all(
+        &mut self,
+        cells: &[(usize, usize)],
+        mine: bool,
+        rule: Rule,
+        sources: &[(usize, usize)],
+    ) {
+        for (x, y) in cells.iter().cloned() {
+            if self.cells.insert((x, y)) {
+                self.deductions.push(Deduction {
+                    x,
+                    y,
+                    mine,
+                    rule,
+                    sources: sources.to_vec(),
+                });
+            }
+        }
+    }
+
+    /// Checks whether `a` forces the tiles it doesn't share with `b`.
+    /// If `a` has so many mines that its tiles outside of `b` must all be mines, then the shared
+    /// tiles hold all of `b`'s mines and the rest of `b` is safe.
+    fn compare(&mut self, a: &Constraint, b: &Constraint, rule: Rule) {
+        let only_a: Vec<(usize, usize)> = a
+            .cells
+            .iter()
+            .filter(|cell| b.cells.binary_search(cell).is_err())
+            .cloned()
+            .collect();
+        if only_a.len() == a.cells.len() || a.mines < b.mines || a.mines - b.mines != only_a.len() {
+            return;
+        }
+        let only_b: Vec<(usize, usize)> = b
+            .cells
+            .iter()
+            .filter(|cell| a.cells.binary_search(cell).is_err())
+            .cloned()
+            .collect();
+        let mut sources = a.sources.clone();
+        sources.extend(b.sources.iter().cloned());
+        self.add_all(&only_a, true, rule, &sources);
+        self.add_all(&only_b, false, rule, &sources);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_numbers_finish_themselves() {
+        let board = Board::from_rows(&["*#.", "...", "..."]);
+        let deductions = solve(&board);
+        // The zero clears its last hidden neighbour, which leaves the ones next to the mine with
+        // only the mine hidden
+        assert_eq!(
+            deductions,
+            vec![
+                Deduction {
+                    x: 1,
+                    y: 0,
+                    mine: false,
+                    rule: Rule::Single,
+                    sources: vec![(2, 0)],
+                },
+                Deduction {
+                    x: 0,
+                    y: 0,
+                    mine: true,
+                    rule: Rule::Single,
+                    sources: vec![(0, 1)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn overlapping_numbers_share_their_mines() {
+        // Every number is a one with at least two hidden tiles around it, but the ones at the
+        // ends hold all the mines of the ones beside them
+        let board = Board::from_rows(&["*##*", "...."]);
+        let mut deductions = View::new(&board).deduce();
+        deductions.sort_by_key(|deduction| deduction.x);
+        let found: Vec<(usize, usize, bool, Rule)> = deductions
+            .iter()
+            .map(|deduction| (deduction.x, deduction.y, deduction.mine, deduction.rule))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(1, 0, false, Rule::Overlap), (2, 0, false, Rule::Overlap)]
+        );
+
+        let mines: Vec<(usize, usize)> = solve(&board)
+            .into_iter()
+            .filter(|deduction| deduction.mine)
+            .map(|deduction| (deduction.x, deduction.y))
+            .collect();
+        assert_eq!(mines, vec![(0, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn the_mine_count_settles_what_the_numbers_cannot() {
+        // The one could be either tile beside it, but its mine is the only one on the board
+        let board = Board::from_rows(&["#.*#"]);
+        let deductions = View::new(&board).deduce();
+        assert_eq!(deductions.len(), 1);
+        assert_eq!((deductions[0].x, deductions[0].y), (3, 0));
+        assert!(!deductions[0].mine);
+        assert_eq!(deductions[0].rule, Rule::MineCount);
+    }
+}
@@ -1,5 +1,14 @@
 use crate::minesweeper::*;
+use crate::placement::{placement_from_name, MinePlacement, Uniform, PLACEMENT_NAMES};
+use crate::probability;
+use crate::replay::{Action, Replay};
+use crate::shape::Shape;
+use crate::solver;
+use crate::topology::{topology_from_name, Cubic, Square, Topology};
+use std::fs;
 use std::io;
+use std::rc::Rc;
+use std::time::Instant;
 
 type Difficulty = (usize, usize, usize);
 
@@ -8,8 +17,13 @@ const MEDIUM: Difficulty = (15, 15, 30);
 const HARD: Difficulty = (30, 15, 99);
 
 pub struct TextGame {
-    board: Board,
+    game: Game,
     state: GameState,
+    replay: Replay,
+    /// When the game clock was last moved on
+    last_tick: Instant,
+    /// The layer of a 3D board being shown and played on
+    layer: usize,
 }
 
 #[derive(PartialEq)]
@@ -26,16 +40,27 @@ impl TextGame {
             .read_line(&mut input)
             .expect("Failed to read line.");
         let input = input.trim().to_lowercase();
-        if input == "easy" {
-            return TextGame::_new(EASY.0, EASY.1, EASY.2);
-        } else if input == "medium" {
-            return TextGame::_new(MEDIUM.0, MEDIUM.1, MEDIUM.2);
-        } else if input == "hard" {
-            return TextGame::_new(HARD.0, HARD.1, HARD.2);
+        let preset = match input.as_str() {
+            "easy" => Some(EASY),
+            "medium" => Some(MEDIUM),
+            "hard" => Some(HARD),
+            _ => None,
+        };
+        if let Some((width, height, mines)) = preset {
+            return TextGame::_new(BoardBuilder::new(width, height).mines(mines))
+                .expect("The difficulties are valid");
+        }
+        loop {
+            match TextGame::_new(TextGame::read_custom()) {
+                Ok(game) => return game,
+                Err(e) => println!("{}, try again.", e),
+            }
         }
+    }
+
+    fn read_custom() -> BoardBuilder {
         let width: usize;
         let height: usize;
-        let mines: usize;
 
         loop {
             println!("Enter the width of the board: ");
@@ -43,9 +68,8 @@ impl TextGame {
             io::stdin()
                 .read_line(&mut input)
                 .expect("Failed to read line.");
-            let input: Result<usize, _> = input.trim().parse();
-            if input.is_ok() {
-                width = input.unwrap();
+            if let Ok(input) = input.trim().parse() {
+                width = input;
                 break;
             }
             println!("You must enter a whole number.");
@@ -56,166 +80,363 @@ impl TextGame {
             io::stdin()
                 .read_line(&mut input)
                 .expect("Failed to read line.");
-            let input: Result<usize, _> = input.trim().parse();
-            if input.is_ok() {
-                height = input.unwrap();
+            if let Ok(input) = input.trim().parse() {
+                height = input;
                 break;
             }
             println!("You must enter a whole number.");
         }
+        let topology = TextGame::read_topology();
+        let layers = topology.layers();
+        // A 3D board keeps its layers one after another down the rows
+        let mut builder = BoardBuilder::new(width, height.saturating_mul(layers))
+            .topology(topology)
+            .wrap(TextGame::read_wrap());
+        if let Some(shape) = TextGame::read_shape(width, height) {
+            builder = builder.shape(shape.stacked(layers));
+        }
+        builder = builder.placement(TextGame::read_placement());
         loop {
-            println!("Enter the number of mines: ");
+            println!("Enter the number of mines, or a percentage of the tiles such as 15%: ");
             let mut input = String::new();
             io::stdin()
                 .read_line(&mut input)
                 .expect("Failed to read line.");
-            let input: Result<usize, _> = input.trim().parse();
-            if input.is_ok() {
-                let input = input.unwrap();
-                if input > width * height {
-                    println!("There can't be more mines than tiles!");
-                    continue;
+            let input = input.trim();
+            if let Some(percent) = input.strip_suffix('%') {
+                if let Ok(percent) = percent.trim().parse::<f64>() {
+                    return builder.density(percent / 100.);
                 }
-                mines = input;
-                break;
+            } else if let Ok(mines) = input.parse() {
+                return builder.mines(mines);
             }
-            println!("You must enter a whole number.");
+            println!("You must enter a whole number or a percentage.");
         }
-        TextGame::_new(width, height, mines)
     }
 
-    fn _new(width: usize, height: usize, mines: usize) -> TextGame {
-        let board = Board::new(width, height, mines);
+    fn read_topology() -> Rc<dyn Topology> {
+        loop {
+            println!(
+                "Enter the shape of the tiles, square, hex, triangle or cube (blank for square),"
+            );
+            println!(
+                "or which squares touch, orthogonal, knight, radius2 or mask:dx/dy;dx/dy...: "
+            );
+            let mut input = String::new();
+            io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read line.");
+            let input = input.trim();
+            if input.is_empty() {
+                return Rc::new(Square);
+            }
+            if input == "cube" {
+                return Rc::new(Cubic::new(TextGame::read_depth()));
+            }
+            if let Some(topology) = topology_from_name(input) {
+                return topology;
+            }
+            println!("You must enter one of the shapes or neighbourhoods listed.");
+        }
+    }
 
-        TextGame {
-            board,
-            state: GameState::Run,
+    fn read_depth() -> usize {
+        loop {
+            println!("Enter the number of layers: ");
+            let mut input = String::new();
+            io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read line.");
+            if let Ok(depth) = input.trim().parse() {
+                if depth > 0 {
+                    return depth;
+                }
+            }
+            println!("You must enter a whole number above 0.");
+        }
+    }
+
+    /// Asks for the outline of the board, which is a plain rectangle if None is returned. A
+    /// template file sets the size of the board itself.
+    fn read_shape(width: usize, height: usize) -> Option<Shape> {
+        loop {
+            println!(
+                "Enter the shape of the board, circle, donut or heart (blank for a rectangle),"
+            );
+            println!("or the path to a text file with the shape drawn in it: ");
+            let mut input = String::new();
+            io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read line.");
+            match input.trim() {
+                "" | "rectangle" => return None,
+                "circle" => return Some(Shape::circle(width, height)),
+                "donut" => return Some(Shape::donut(width, height)),
+                "heart" => return Some(Shape::heart(width, height)),
+                path => match fs::read_to_string(path) {
+                    Ok(template) => return Some(Shape::from_template(&template)),
+                    Err(e) => println!("Unable to read {}: {}", path, e),
+                },
+            }
+        }
+    }
+
+    fn read_placement() -> Rc<dyn MinePlacement> {
+        loop {
+            println!(
+                "Enter how the mines are spread, {} (blank for uniform): ",
+                PLACEMENT_NAMES.join(", ")
+            );
+            let mut input = String::new();
+            io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read line.");
+            let input = input.trim().to_lowercase();
+            if input.is_empty() {
+                return Rc::new(Uniform);
+            }
+            if let Some(placement) = placement_from_name(&input) {
+                return placement;
+            }
+            println!("You must enter one of the placements listed.");
+        }
+    }
+
+    fn read_wrap() -> bool {
+        loop {
+            println!("Should the edges of the board wrap around? (y/n): ");
+            let mut input = String::new();
+            io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read line.");
+            let input = input.trim().to_lowercase();
+            if input == "y" || input == "yes" {
+                return true;
+            } else if input == "n" || input == "no" {
+                return false;
+            }
+            println!("You must enter y or n.");
+        }
+    }
+
+    fn read_first_click() -> FirstClickPolicy {
+        loop {
+            println!("Enter the first click policy, unprotected, safe, opening, no-guess or kind");
+            println!("(blank for opening): ");
+            let mut input = String::new();
+            io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read line.");
+            let input = input.trim().to_lowercase();
+            if input.is_empty() {
+                return FirstClickPolicy::Opening;
+            }
+            if let Some(first_click) = FirstClickPolicy::from_name(&input) {
+                return first_click;
+            }
+            println!("You must enter one of the policies listed.");
         }
     }
 
+    fn read_seed() -> Option<u64> {
+        loop {
+            println!("Enter a board seed to replay a board, or leave it blank for a random one: ");
+            let mut input = String::new();
+            io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read line.");
+            let input = input.trim();
+            if input.is_empty() {
+                return None;
+            }
+            if let Ok(seed) = input.parse() {
+                return Some(seed);
+            }
+            println!("The seed must be a whole number.");
+        }
+    }
+
+    fn _new(mut builder: BoardBuilder) -> Result<TextGame, BoardError> {
+        builder = builder.first_click(TextGame::read_first_click());
+        if let Some(seed) = TextGame::read_seed() {
+            builder = builder.seed(seed);
+        }
+        let board = builder.build()?;
+
+        let replay = Replay::new(&board);
+        Ok(TextGame {
+            game: Game::new(board),
+            state: GameState::Run,
+            replay,
+            last_tick: Instant::now(),
+            layer: 0,
+        })
+    }
+
     fn check(&mut self, x: usize, y: usize) {
-        let result = self.board.reveal_at(x, y);
-        if let Ok(tile) = result {
-            if tile == Tile::Mine {
-                self.board.reveal_all();
-                self.game_over(true);
+        match self.game.reveal(x, y) {
+            Ok(_) => {
+                self.record(Action::Reveal(x, y));
+                self.check_game_over();
             }
+            Err(BoardError::AlreadyRevealed { .. }) => println!("That square is already revealed."),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    /// Ends the game if the last move won or lost it.
+    fn check_game_over(&mut self) {
+        self.report_events();
+        match self.game.phase() {
+            Phase::Lost => self.game_over(true),
+            Phase::Won => self.game_over(false),
+            _ => (),
         }
     }
 
     fn toggle(&mut self, x: usize, y: usize) {
-        self.board.toggle_display_at(x, y).unwrap();
+        match self.game.toggle(x, y) {
+            Ok(_) => self.record(Action::Toggle(x, y)),
+            Err(BoardError::AlreadyRevealed { .. }) => {
+                println!("Revealed squares can't be flagged.")
+            }
+            Err(e) => println!("{}", e),
+        }
     }
 
     fn flag(&mut self, x: usize, y: usize) {
-        let cur_display = self
-            .board
-            .get_display_at(x, y)
-            .expect("Tried to flag an invalid tile");
-        match cur_display {
-            TileDisplay::Hidden => {
-                self.board.toggle_display_at(x, y).unwrap();
+        match self.game.board().get_display_at(x, y) {
+            Ok(TileDisplay::Hidden) => {
+                self.toggle(x, y);
             }
-            TileDisplay::Question => {
-                self.board.toggle_display_at(x, y).unwrap();
-                self.board.toggle_display_at(x, y).unwrap();
+            Ok(TileDisplay::Question) => {
+                self.toggle(x, y);
+                self.toggle(x, y);
             }
-            _ => (),
+            Ok(_) => (),
+            Err(e) => println!("{}", e),
         };
     }
 
     fn chord(&mut self, x: usize, y: usize) {
-        let display_at = self
-            .board
-            .get_display_at(x, y)
-            .expect("Tried to chord at invalid tile.");
-        if display_at != TileDisplay::Revealed {
-            println!("Cannot chord from a tile that is not revealed.");
+        match self.game.chord(x, y) {
+            Ok(_) => {
+                self.record(Action::Chord(x, y));
+                self.check_game_over();
+            }
+            Err(BoardError::NotRevealed { .. }) => {
+                println!("Cannot chord from a tile that is not revealed.")
+            }
+            Err(BoardError::WrongFlagCount { .. }) => println!(
+                "Chording is only allowed when there are exactly the right number of flags adjacent to a tile."
+            ),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    fn undo(&mut self) {
+        let undone = self.game.undo();
+        if undone == 0 {
+            println!("There is nothing to undo.");
+        }
+        for _ in 0..undone {
+            self.record(Action::Undo);
+        }
+    }
+
+    fn redo(&mut self) {
+        if !self.game.redo() {
+            println!("There is nothing to redo.");
             return;
-        };
-        let tile = self.board.reveal_at(x, y).unwrap();
-        let req_flags = match tile {
-            Tile::Mine => panic!("How did we get here?(Trying to chord a mine)"),
-            Tile::Safe(digit) => Digit::to_int(digit),
-        };
-        let mut count_flags = 0;
-        count_flags += match self.board.get_display_at(x, y + 1) {
-            Ok(display) => match display {
-                TileDisplay::Flag => 1,
-                _ => 0,
-            },
-            Err(_) => 0,
-        };
-        if y > 0 {
-            count_flags += match self.board.get_display_at(x + 1, y - 1) {
-                Ok(display) => match display {
-                    TileDisplay::Flag => 1,
-                    _ => 0,
-                },
-                Err(_) => 0,
-            };
-            count_flags += match self.board.get_display_at(x, y - 1) {
-                Ok(display) => match display {
-                    TileDisplay::Flag => 1,
-                    _ => 0,
-                },
-                Err(_) => 0,
-            };
         }
-        count_flags += match self.board.get_display_at(x + 1, y) {
-            Ok(display) => match display {
-                TileDisplay::Flag => 1,
-                _ => 0,
-            },
-            Err(_) => 0,
-        };
-        count_flags += match self.board.get_display_at(x + 1, y + 1) {
-            Ok(display) => match display {
-                TileDisplay::Flag => 1,
-                _ => 0,
-            },
-            Err(_) => 0,
-        };
-        if x > 0 {
-            if y > 0 {
-                count_flags += match self.board.get_display_at(x - 1, y - 1) {
-                    Ok(display) => match display {
-                        TileDisplay::Flag => 1,
-                        _ => 0,
-                    },
-                    Err(_) => 0,
-                };
+        self.record(Action::Redo);
+        self.check_game_over();
+    }
+
+    /// Moves the game clock on by the time since it was last moved on.
+    fn tick(&mut self) {
+        self.game.tick(self.last_tick.elapsed().as_secs_f64());
+        self.last_tick = Instant::now();
+    }
+
+    fn record(&mut self, action: Action) {
+        self.replay.record(self.game.time(), action);
+    }
+
+    /// Offers to write a replay of the finished game to a file.
+    fn save_replay(&mut self) {
+        self.replay.finish(self.game.board());
+        loop {
+            println!("Enter a file name to save a replay of this game, or leave it blank: ");
+            let mut input = String::new();
+            io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read line.");
+            let path = input.trim();
+            if path.is_empty() {
+                return;
             }
-            count_flags += match self.board.get_display_at(x - 1, y) {
-                Ok(display) => match display {
-                    TileDisplay::Flag => 1,
-                    _ => 0,
-                },
-                Err(_) => 0,
-            };
-            count_flags += match self.board.get_display_at(x - 1, y + 1) {
-                Ok(display) => match display {
-                    TileDisplay::Flag => 1,
-                    _ => 0,
-                },
-                Err(_) => 0,
-            };
+            match fs::write(path, self.replay.save()) {
+                Ok(_) => return,
+                Err(e) => println!("Unable to save the replay: {}", e),
+            }
+        }
+    }
+
+    fn hint(&self) {
+        let deductions = solver::solve(self.game.board());
+        if deductions.is_empty() {
+            println!("Nothing can be deduced, you will have to guess.");
+        }
+        for deduction in deductions {
+            println!("{}", deduction.describe(|x, y| self.describe(x, y)));
         }
-        if count_flags == req_flags {
-            self.board.reveal_adjacent(x, y).unwrap();
+    }
+
+    /// Prints the chance of a mine on each tile of the board, or of the layer being played on if
+    /// it has more than one, with rows numbered the way they are entered.
+    fn print_probabilities(&self) {
+        let board = self.game.board();
+        let probabilities = probability::mine_probabilities(board);
+        if board.depth() > 1 {
+            println!(
+                "Chance of a mine on each square of layer {}, in percent:",
+                self.layer
+            );
         } else {
-            println!("Chording is only allowed when there are exactly the right number of flags adjacent to a tile.");
+            println!("Chance of a mine on each square, in percent:");
+        }
+        print!("   ");
+        for x in 0..board.width {
+            print!("{:>4}", x);
+        }
+        println!();
+        let rows = board.layer_height();
+        for row in 0..rows {
+            let y = self.layer * rows + row;
+            print!("{:2} ", row);
+            for column in probabilities.iter() {
+                match column[y] {
+                    Some(p) => print!("{:>4}", (p * 100.).round() as u32),
+                    None => print!("   -"),
+                }
+            }
+            println!();
         }
     }
 
     fn game_over(&mut self, lose: bool) {
+        let stats = self.game.stats();
         if lose {
             println!("You Lose!");
-            self.board.reveal_all();
+            self.game.reveal_all();
+            self.record(Action::RevealAll);
         } else {
             println!("You Win!");
         }
-        println!("{}", self.board);
+        println!("{}", self.game.board());
+        println!("Board seed: {}", self.game.board().seed);
+        println!("{}", stats);
         self.state = GameState::End;
     }
 
@@ -227,6 +448,11 @@ impl TextGame {
         println!("Toggle square - 'toggle x y' or 't x y'");
         println!("Flag square - 'flag x y' or 'f x y'");
         println!("Chord at square - 'chord x y' or 'ch x y'");
+        println!("Show another layer of a 3D board - 'layer z' or 'l z'");
+        println!("Undo the last move - 'undo' or 'u'");
+        println!("Redo an undone move - 'redo' or 'r'");
+        println!("Show what can be deduced - 'hint' or 'h'");
+        println!("Show the chance of a mine on each square - 'probabilities' or 'p'");
         println!("Show this menu - 'menu' or 'm'");
         println!("Quit game - 'quit' or 'q'");
     }
@@ -234,17 +460,31 @@ impl TextGame {
     pub fn main_loop(&mut self) {
         loop {
             if self.state == GameState::End {
+                println!("Enter 'undo' to take back your last move, or anything else to finish: ");
+                let mut input = String::new();
+                std::io::stdin()
+                    .read_line(&mut input)
+                    .expect("Failed to read line.");
+                let input = input.trim().to_lowercase();
+                if input == "u" || input == "undo" {
+                    self.undo();
+                    self.state = GameState::Run;
+                    continue;
+                }
+                self.save_replay();
                 break;
             }
-            println!("{}", self.board);
+            self.print_board();
+            println!("Mines left: {}", self.game.mines_left());
             println!("Enter your selection(menu for options): ");
             let mut input = String::new();
             std::io::stdin()
                 .read_line(&mut input)
                 .expect("Failed to read line.");
+            self.tick();
             let input = input.to_lowercase();
             let input: Vec<&str> = input.split_whitespace().collect();
-            if input.len() < 1 {
+            if input.is_empty() {
                 println!("You must select an option.");
                 continue;
             }
@@ -254,6 +494,26 @@ impl TextGame {
                 continue;
             } else if option == "q" || option == "quit" {
                 self.game_over(true);
+                self.save_replay();
+                break;
+            } else if option == "u" || option == "undo" {
+                self.undo();
+                continue;
+            } else if option == "r" || option == "redo" {
+                self.redo();
+                continue;
+            } else if option == "h" || option == "hint" {
+                self.hint();
+                continue;
+            } else if option == "p" || option == "probabilities" {
+                self.print_probabilities();
+                continue;
+            } else if option == "l" || option == "layer" {
+                let depth = self.game.board().depth();
+                match input.get(1).map(|z| z.parse::<usize>()) {
+                    Some(Ok(z)) if z < depth => self.layer = z,
+                    _ => println!("The layer must be a whole number less than {}", depth),
+                }
                 continue;
             }
             if input.len() < 3 {
@@ -262,8 +522,8 @@ impl TextGame {
             }
             let x: usize = match input[1].parse() {
                 Ok(val) => {
-                    if val >= self.board.width {
-                        println!("x must be less than {}", self.board.width);
+                    if val >= self.game.board().width {
+                        println!("x must be less than {}", self.game.board().width);
                         continue;
                     };
                     val
@@ -273,13 +533,14 @@ impl TextGame {
                     continue;
                 }
             };
-            let y: usize = match input[2].parse() {
+            let y: usize = match input[2].parse::<usize>() {
                 Ok(val) => {
-                    if val >= self.board.height {
-                        println!("y must be less than {}", self.board.height);
+                    let rows = self.game.board().layer_height();
+                    if val >= rows {
+                        println!("y must be less than {}", rows);
                         continue;
                     };
-                    val
+                    self.layer * rows + val
                 }
                 Err(_) => {
                     println!("y must be a whole number");
@@ -295,9 +556,78 @@ impl TextGame {
             } else if option == "ch" || option == "chord" {
                 self.chord(x, y);
             }
-            if self.state != GameState::End && self.board.check_victory() {
-                self.game_over(false);
+        }
+    }
+
+    /// Prints the board, or just the layer being played on if it has more than one.
+    fn print_board(&self) {
+        let board = self.game.board();
+        if board.depth() > 1 {
+            println!("Layer {} of {}:", self.layer, board.depth());
+            println!("{}", board.layer(self.layer));
+        } else {
+            println!("{}", board);
+        }
+    }
+
+    /// Writes out the coordinates of a tile the way they are entered, with the layer last on a
+    /// 3D board.
+    fn describe(&self, x: usize, y: usize) -> String {
+        let board = self.game.board();
+        if board.depth() > 1 {
+            let rows = board.layer_height();
+            format!("({}, {}, layer {})", x, y % rows, y / rows)
+        } else {
+            format!("({}, {})", x, y)
+        }
+    }
+
+    /// Describes what the last command did that isn't plain from the board.
+    fn report_events(&mut self) {
+        for event in self.game.drain_events() {
+            match event {
+                BoardEvent::OpeningCascaded { x, y, cells } => {
+                    println!("Opened up {} squares from {}.", cells, self.describe(x, y))
+                }
+                BoardEvent::MineExploded { x, y } => {
+                    println!("Mine exploded at {}!", self.describe(x, y))
+                }
+                _ => (),
             }
         }
     }
 }
+
+/// Plays back a replay file step by step, then checks that it ends on the recorded board.
+pub fn play_replay() {
+    let replay = loop {
+        println!("Enter the file name of the replay: ");
+        let mut input = String::new();
+        io::stdin()
+            .read_line(&mut input)
+            .expect("Failed to read line.");
+        let loaded = fs::read_to_string(input.trim())
+            .map_err(|e| e.to_string())
+            .and_then(|save| Replay::load(&save));
+        match loaded {
+            Ok(replay) => break replay,
+            Err(message) => println!("Unable to load the replay: {}", message),
+        }
+    };
+    // The starting board was checked when the replay was loaded
+    let mut board = replay
+        .start_board()
+        .expect("Replay has a valid starting board");
+    println!("{}", board);
+    for step in replay.steps.iter() {
+        println!("{:.3}s: {}", step.time, step.action);
+        if let Err(e) = step.action.apply(&mut board) {
+            println!("{}", e);
+        }
+        println!("{}", board);
+    }
+    match replay.play() {
+        Ok(_) => println!("The replay ended on the recorded board."),
+        Err(message) => println!("{}", message),
+    }
+}
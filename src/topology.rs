@@ -0,0 +1,362 @@
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// How the tiles of a board are laid out and which of them touch. Tiles are always addressed by
+/// column and row within a `width` by `height` board, but what those coordinates mean depends on
+/// the topology.
+pub trait Topology: Debug {
+    /// The name the topology is saved under, as read by `topology_from_name`.
+    fn name(&self) -> String;
+
+    /// The column and row offsets from (x, y) to each tile adjacent to it on an unbounded board.
+    fn offsets(&self, x: usize, y: usize) -> Vec<(isize, isize)>;
+
+    /// Returns true if a `width` by `height` board can wrap around without tiles on opposite
+    /// edges disagreeing about whether they touch.
+    fn can_wrap(&self, _width: usize, _height: usize) -> bool {
+        true
+    }
+
+    /// Returns the coordinates of every tile adjacent to (x, y). When `wrap` is set, offsets that
+    /// go off one edge of the board come back on the opposite edge.
+    fn neighbours(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        wrap: bool,
+    ) -> Vec<(usize, usize)> {
+        let mut neighbours = Vec::new();
+        for (dx, dy) in self.offsets(x, y) {
+            let tile = if wrap {
+                (wrap_add(x, dx, width), wrap_add(y, dy, height))
+            } else {
+                match (x.checked_add_signed(dx), y.checked_add_signed(dy)) {
+                    (Some(nx), Some(ny)) if nx < width && ny < height => (nx, ny),
+                    _ => continue,
+                }
+            };
+            // On a narrow wrapped board different offsets can land on the same tile, or on (x, y)
+            if tile != (x, y) && !neighbours.contains(&tile) {
+                neighbours.push(tile);
+            }
+        }
+        neighbours
+    }
+
+    /// The most neighbours any tile on a `width` by `height` board has.
+    fn most_neighbours(&self, width: usize, height: usize, wrap: bool) -> usize {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| self.neighbours(x, y, width, height, wrap).len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// How many layers the rows of the board are split into, each `height / layers` rows high.
+    fn layers(&self) -> usize {
+        1
+    }
+
+    /// How many characters row `y` is indented by when the board is printed with tiles
+    /// `tile_width` characters wide.
+    fn row_indent(&self, _y: usize, _tile_width: usize) -> usize {
+        0
+    }
+
+    /// The characters printed either side of the tile at (x, y).
+    fn tile_edges(&self, _x: usize, _y: usize) -> (char, char) {
+        ('|', '|')
+    }
+}
+
+/// Square tiles, each touching the eight around it.
+#[derive(Debug, Copy, Clone)]
+pub struct Square;
+
+impl Topology for Square {
+    fn name(&self) -> String {
+        "square".to_owned()
+    }
+
+    fn offsets(&self, _x: usize, _y: usize) -> Vec<(isize, isize)> {
+        vec![
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ]
+    }
+}
+
+/// Hexagonal tiles in rows, with every odd row shifted half a tile to the right. Each tile
+/// touches two in its own row and two in each of the rows above and below.
+#[derive(Debug, Copy, Clone)]
+pub struct Hexagonal;
+
+impl Topology for Hexagonal {
+    fn name(&self) -> String {
+        "hex".to_owned()
+    }
+
+    fn offsets(&self, _x: usize, y: usize) -> Vec<(isize, isize)> {
+        // The tiles above and below an even row are up and to the left, on an odd row they are up
+        // and to the right
+        let (left, right) = if y.is_multiple_of(2) { (-1, 0) } else { (0, 1) };
+        vec![
+            (-1, 0),
+            (1, 0),
+            (left, -1),
+            (right, -1),
+            (left, 1),
+            (right, 1),
+        ]
+    }
+
+    /// The top and bottom rows only line up if one is shifted and the other isn't.
+    fn can_wrap(&self, _width: usize, height: usize) -> bool {
+        height.is_multiple_of(2)
+    }
+
+    fn row_indent(&self, y: usize, tile_width: usize) -> usize {
+        if y.is_multiple_of(2) {
+            0
+        } else {
+            tile_width.div_ceil(2)
+        }
+    }
+
+    fn tile_edges(&self, _x: usize, _y: usize) -> (char, char) {
+        ('<', '>')
+    }
+}
+
+/// Triangular tiles in rows, alternately pointing up and down. Each tile touches the tiles it
+/// shares an edge with: the two beside it, and the one above or below its flat side.
+#[derive(Debug, Copy, Clone)]
+pub struct Triangular;
+
+impl Triangular {
+    fn points_up(x: usize, y: usize) -> bool {
+        (x + y).is_multiple_of(2)
+    }
+}
+
+impl Topology for Triangular {
+    fn name(&self) -> String {
+        "triangle".to_owned()
+    }
+
+    fn offsets(&self, x: usize, y: usize) -> Vec<(isize, isize)> {
+        let flat_side = if Triangular::points_up(x, y) { 1 } else { -1 };
+        vec![(-1, 0), (1, 0), (0, flat_side)]
+    }
+
+    /// Tiles on opposite edges only fit together if they point opposite ways.
+    fn can_wrap(&self, width: usize, height: usize) -> bool {
+        width.is_multiple_of(2) && height.is_multiple_of(2)
+    }
+
+    fn tile_edges(&self, x: usize, y: usize) -> (char, char) {
+        if Triangular::points_up(x, y) {
+            ('/', '\\')
+        } else {
+            ('\\', '/')
+        }
+    }
+}
+
+/// Cubes stacked in layers, each touching the 26 around it. The layers are stored one after
+/// another down the rows of the board, so on a board `height` rows high each layer has
+/// `height / depth` rows.
+#[derive(Debug, Copy, Clone)]
+pub struct Cubic {
+    depth: usize,
+}
+
+impl Cubic {
+    pub fn new(depth: usize) -> Cubic {
+        assert!(depth > 0, "A board needs at least one layer");
+        Cubic { depth }
+    }
+}
+
+impl Topology for Cubic {
+    fn name(&self) -> String {
+        format!("cube:{}", self.depth)
+    }
+
+    /// The offsets within a layer, which are the same as for squares. `Cubic::neighbours` adds
+    /// the layers above and below.
+    fn offsets(&self, x: usize, y: usize) -> Vec<(isize, isize)> {
+        Square.offsets(x, y)
+    }
+
+    fn layers(&self) -> usize {
+        self.depth
+    }
+
+    fn neighbours(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        wrap: bool,
+    ) -> Vec<(usize, usize)> {
+        let rows = height / self.depth;
+        if rows == 0 {
+            return Vec::new();
+        }
+        let (z, row) = (y / rows, y % rows);
+        let mut neighbours = Vec::with_capacity(26);
+        for dz in -1..=1 {
+            for (dx, dy) in std::iter::once((0, 0)).chain(self.offsets(x, y)) {
+                if dz == 0 && (dx, dy) == (0, 0) {
+                    continue;
+                }
+                let tile = if wrap {
+                    (
+                        wrap_add(x, dx, width),
+                        wrap_add(z, dz, self.depth) * rows + wrap_add(row, dy, rows),
+                    )
+                } else {
+                    match (
+                        x.checked_add_signed(dx),
+                        row.checked_add_signed(dy),
+                        z.checked_add_signed(dz),
+                    ) {
+                        (Some(nx), Some(nrow), Some(nz))
+                            if nx < width && nrow < rows && nz < self.depth =>
+                        {
+                            (nx, nz * rows + nrow)
+                        }
+                        _ => continue,
+                    }
+                };
+                if tile != (x, y) && !neighbours.contains(&tile) {
+                    neighbours.push(tile);
+                }
+            }
+        }
+        neighbours
+    }
+}
+
+/// Square tiles where the tiles at a chosen set of offsets count as touching, rather than the
+/// eight around each tile.
+#[derive(Debug, Clone)]
+pub struct Mask {
+    /// The name of the preset the mask came from, if any
+    preset: Option<&'static str>,
+    offsets: Vec<(isize, isize)>,
+}
+
+impl Mask {
+    /// Makes a mask from column and row offsets. Tiles always touch each other both ways, so the
+    /// reverse of every offset is added too, and a tile never touches itself.
+    pub fn new(offsets: &[(isize, isize)]) -> Mask {
+        let mut mask = Mask {
+            preset: None,
+            offsets: Vec::with_capacity(offsets.len() * 2),
+        };
+        for &(dx, dy) in offsets {
+            for offset in [(dx, dy), (-dx, -dy)] {
+                if offset != (0, 0) && !mask.offsets.contains(&offset) {
+                    mask.offsets.push(offset);
+                }
+            }
+        }
+        mask
+    }
+
+    /// The four tiles sharing an edge with each tile.
+    pub fn orthogonal() -> Mask {
+        Mask {
+            preset: Some("orthogonal"),
+            ..Mask::new(&[(1, 0), (0, 1)])
+        }
+    }
+
+    /// The eight tiles a knight's move away from each tile.
+    pub fn knight() -> Mask {
+        Mask {
+            preset: Some("knight"),
+            ..Mask::new(&[(1, 2), (2, 1), (2, -1), (1, -2)])
+        }
+    }
+
+    /// The 24 tiles up to two rows and columns away from each tile.
+    pub fn radius2() -> Mask {
+        let offsets: Vec<(isize, isize)> = (-2..=2)
+            .flat_map(|dx| (-2..=2).map(move |dy| (dx, dy)))
+            .collect();
+        Mask {
+            preset: Some("radius2"),
+            ..Mask::new(&offsets)
+        }
+    }
+
+    /// Reads the offsets of a custom mask, written as `dx/dy` pairs separated by semicolons.
+    fn parse(offsets: &str) -> Option<Mask> {
+        let offsets = offsets
+            .split(';')
+            .filter(|offset| !offset.is_empty())
+            .map(|offset| {
+                let (dx, dy) = offset.split_once('/')?;
+                Some((dx.trim().parse().ok()?, dy.trim().parse().ok()?))
+            })
+            .collect::<Option<Vec<(isize, isize)>>>()?;
+        Some(Mask::new(&offsets))
+    }
+}
+
+impl Topology for Mask {
+    fn name(&self) -> String {
+        match self.preset {
+            Some(preset) => preset.to_owned(),
+            None => {
+                let offsets: Vec<String> = self
+                    .offsets
+                    .iter()
+                    .map(|(dx, dy)| format!("{}/{}", dx, dy))
+                    .collect();
+                format!("mask:{}", offsets.join(";"))
+            }
+        }
+    }
+
+    fn offsets(&self, _x: usize, _y: usize) -> Vec<(isize, isize)> {
+        self.offsets.clone()
+    }
+}
+
+/// Returns the topology saved under `name` by `Topology::name`. Custom masks are named `mask:`
+/// followed by their offsets, such as `mask:1/0;0/1`, and cubes `cube:` followed by their depth.
+pub fn topology_from_name(name: &str) -> Option<Rc<dyn Topology>> {
+    match name {
+        "square" => Some(Rc::new(Square)),
+        "hex" => Some(Rc::new(Hexagonal)),
+        "triangle" => Some(Rc::new(Triangular)),
+        "orthogonal" => Some(Rc::new(Mask::orthogonal())),
+        "knight" => Some(Rc::new(Mask::knight())),
+        "radius2" => Some(Rc::new(Mask::radius2())),
+        _ => {
+            if let Some(depth) = name.strip_prefix("cube:") {
+                let depth = depth.parse().ok().filter(|&depth| depth > 0)?;
+                return Some(Rc::new(Cubic::new(depth)));
+            }
+            Some(Rc::new(Mask::parse(name.strip_prefix("mask:")?)?))
+        }
+    }
+}
+
+/// Adds `offset` to `position`, wrapping around a dimension of the given size.
+fn wrap_add(position: usize, offset: isize, size: usize) -> usize {
+    (position as isize + offset).rem_euclid(size as isize) as usize
+}